@@ -46,6 +46,9 @@ class SysMemInfo final {
     static constexpr const char kMemPageTables[] = "PageTables:";
     static constexpr const char kMemKernelStack[] = "KernelStack:";
     static constexpr const char kMemKReclaimable[] = "KReclaimable:";
+    // Not part of the default tags, read them with ReadMemInfo(ntags, tags, out)
+    static constexpr const char kMemUnevictable[] = "Unevictable:";
+    static constexpr const char kMemMlocked[] = "Mlocked:";
 
     static constexpr std::initializer_list<std::string_view> kDefaultSysMemInfoTags = {
             SysMemInfo::kMemTotal,      SysMemInfo::kMemFree,        SysMemInfo::kMemBuffers,
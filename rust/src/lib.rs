@@ -0,0 +1,82 @@
+// Copyright (C) 2026 The Android Open Source Project
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Native Rust counterpart of libmeminfo.
+//!
+//! The API mirrors the C++ classes in `include/meminfo` so that Rust services can query
+//! system and process memory accounting without linking the C++ library or shelling out to
+//! the command line tools.
+
+pub mod meminfo;
+pub mod pageacct;
+pub mod procmeminfo;
+pub mod sysmeminfo;
+
+pub use meminfo::{MemUsage, Vma};
+pub use pageacct::PageAcct;
+pub use procmeminfo::ProcMemInfo;
+pub use sysmeminfo::SysMemInfo;
+
+use std::sync::OnceLock;
+
+/// Returns the runtime page size of the system in bytes.
+pub fn page_size() -> u64 {
+    static PAGE_SIZE: OnceLock<u64> = OnceLock::new();
+    // SAFETY: sysconf() has no memory safety requirements.
+    *PAGE_SIZE.get_or_init(|| unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64)
+}
+
+#[cfg(test)]
+mod test_utils {
+    use std::env;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+    use std::process;
+
+    // Test data is installed next to the test binary, like for libmeminfo_test.
+    pub fn testdata_dir(name: &str) -> PathBuf {
+        env::current_exe().unwrap().parent().unwrap().join(name)
+    }
+
+    /// A file or directory in the temp dir that is removed when it goes out of scope.
+    pub struct TempPath(PathBuf);
+
+    impl TempPath {
+        pub fn new(name: &str) -> Self {
+            Self(env::temp_dir().join(format!("meminfo_test_{}_{}", process::id(), name)))
+        }
+    }
+
+    impl AsRef<Path> for TempPath {
+        fn as_ref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempPath {
+        fn drop(&mut self) {
+            if self.0.is_dir() {
+                let _ = fs::remove_dir_all(&self.0);
+            } else {
+                let _ = fs::remove_file(&self.0);
+            }
+        }
+    }
+
+    pub fn write_temp_file(name: &str, content: &str) -> TempPath {
+        let path = TempPath::new(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+}
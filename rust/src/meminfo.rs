@@ -0,0 +1,154 @@
+// Copyright (C) 2026 The Android Open Source Project
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Memory accounting types shared by the process level APIs, the Rust equivalent of
+//! `meminfo/meminfo.h`.
+
+/// Memory usage of a process or a single mapping.
+///
+/// Values read from /proc/<pid>/smaps are in kB, values computed from /proc/<pid>/pagemap are
+/// in bytes, exactly like the C++ library.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MemUsage {
+    pub vss: u64,
+    pub rss: u64,
+    pub pss: u64,
+    pub uss: u64,
+
+    pub swap: u64,
+    pub swap_pss: u64,
+
+    pub private_clean: u64,
+    pub private_dirty: u64,
+    pub shared_clean: u64,
+    pub shared_dirty: u64,
+}
+
+impl MemUsage {
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Adds all counters of `from` to this usage.
+    pub fn add(&mut self, from: &MemUsage) {
+        self.vss += from.vss;
+        self.rss += from.rss;
+        self.pss += from.pss;
+        self.uss += from.uss;
+
+        self.swap += from.swap;
+        self.swap_pss += from.swap_pss;
+
+        self.private_clean += from.private_clean;
+        self.private_dirty += from.private_dirty;
+
+        self.shared_clean += from.shared_clean;
+        self.shared_dirty += from.shared_dirty;
+    }
+}
+
+/// A virtual memory area of a process as described by /proc/<pid>/maps.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Vma {
+    pub start: u64,
+    pub end: u64,
+    pub offset: u64,
+    pub flags: u16,
+    pub name: String,
+
+    /// Memory usage of this mapping.
+    pub usage: MemUsage,
+}
+
+impl Vma {
+    // Permission bits of 'flags', same values as PROT_* from <sys/mman.h>.
+    pub const PROT_READ: u16 = 0x1;
+    pub const PROT_WRITE: u16 = 0x2;
+    pub const PROT_EXEC: u16 = 0x4;
+
+    pub fn new(start: u64, end: u64, offset: u64, flags: u16, name: &str) -> Self {
+        Self { start, end, offset, flags, name: name.to_string(), usage: MemUsage::default() }
+    }
+
+    pub fn clear(&mut self) {
+        self.usage.clear();
+    }
+
+    /// Parses a single line in the /proc/<pid>/maps format, e.g.
+    /// 00400000-00409000 r-xp 00000000 fc:00 426998  /usr/lib/gvfs/gvfsd-http
+    pub fn from_maps_line(line: &str) -> Option<Self> {
+        let mut rest = line;
+        let (start, end) = next_field(&mut rest)?.split_once('-')?;
+        let perms = next_field(&mut rest)?.as_bytes();
+        let offset = next_field(&mut rest)?;
+        // device and inode
+        next_field(&mut rest)?;
+        next_field(&mut rest)?;
+
+        let mut flags = 0;
+        if perms.first() == Some(&b'r') {
+            flags |= Self::PROT_READ;
+        }
+        if perms.get(1) == Some(&b'w') {
+            flags |= Self::PROT_WRITE;
+        }
+        if perms.get(2) == Some(&b'x') {
+            flags |= Self::PROT_EXEC;
+        }
+
+        Some(Self::new(
+            u64::from_str_radix(start, 16).ok()?,
+            u64::from_str_radix(end, 16).ok()?,
+            u64::from_str_radix(offset, 16).ok()?,
+            flags,
+            rest.trim(),
+        ))
+    }
+}
+
+// Returns the next whitespace separated field of 's' and advances 's' past it.
+fn next_field<'a>(s: &mut &'a str) -> Option<&'a str> {
+    let trimmed = s.trim_start();
+    if trimmed.is_empty() {
+        return None;
+    }
+    let end = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+    let (field, rest) = trimmed.split_at(end);
+    *s = rest;
+    Some(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vma_from_maps_line() {
+        let vma = Vma::from_maps_line(
+            "12c00000-13440000 rw-p 00001000 00:00 0          [anon:dalvik-main space (region space)]",
+        )
+        .unwrap();
+        assert_eq!(vma.start, 0x12c00000);
+        assert_eq!(vma.end, 0x13440000);
+        assert_eq!(vma.offset, 0x1000);
+        assert_eq!(vma.flags, Vma::PROT_READ | Vma::PROT_WRITE);
+        assert_eq!(vma.name, "[anon:dalvik-main space (region space)]");
+
+        let vma = Vma::from_maps_line("7f0000-7f1000 ---p 00000000 00:00 0").unwrap();
+        assert_eq!(vma.flags, 0);
+        assert_eq!(vma.name, "");
+
+        assert!(Vma::from_maps_line("Rss:                2652 kB").is_none());
+    }
+}
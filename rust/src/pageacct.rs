@@ -0,0 +1,174 @@
+// Copyright (C) 2026 The Android Open Source Project
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per-page accounting using kernel interfaces like kpagecount, kpageflags etc., the Rust
+//! equivalent of `meminfo/pageacct.h`.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+const KPAGECOUNT_PATH: &str = "/proc/kpagecount";
+const KPAGEFLAGS_PATH: &str = "/proc/kpageflags";
+const PAGE_IDLE_BITMAP_PATH: &str = "/sys/kernel/mm/page_idle/bitmap";
+
+// Page flags from /proc/kpageflags, see <linux/kernel-page-flags.h>
+pub const KPF_REFERENCED: u64 = 2;
+pub const KPF_DIRTY: u64 = 4;
+pub const KPF_ANON: u64 = 12;
+pub const KPF_SWAPBACKED: u64 = 14;
+pub const KPF_UNEVICTABLE: u64 = 18;
+pub const KPF_KSM: u64 = 21;
+pub const KPF_THP: u64 = 22;
+
+/// Reads page flags and map counts of physical pages. Files are opened lazily on first use.
+#[derive(Debug, Default)]
+pub struct PageAcct {
+    kpagecount: Option<File>,
+    kpageflags: Option<File>,
+    pageidle: Option<File>,
+}
+
+impl PageAcct {
+    /// Returns if the kernel supports idle page tracking.
+    pub fn kernel_has_page_idle() -> bool {
+        OpenOptions::new().read(true).write(true).open(PAGE_IDLE_BITMAP_PATH).is_ok()
+    }
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn init_page_acct(&mut self, pageidle_enable: bool) -> io::Result<()> {
+        if pageidle_enable && !Self::kernel_has_page_idle() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Idle page tracking is not supported by the kernel",
+            ));
+        }
+
+        if self.kpagecount.is_none() {
+            self.kpagecount = Some(File::open(KPAGECOUNT_PATH)?);
+        }
+        if self.kpageflags.is_none() {
+            self.kpageflags = Some(File::open(KPAGEFLAGS_PATH)?);
+        }
+        if pageidle_enable && self.pageidle.is_none() {
+            self.pageidle =
+                Some(OpenOptions::new().read(true).write(true).open(PAGE_IDLE_BITMAP_PATH)?);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the flags of the page with frame number `pfn`.
+    pub fn page_flags(&mut self, pfn: u64) -> io::Result<u64> {
+        if self.kpageflags.is_none() {
+            self.init_page_acct(false)?;
+        }
+        read_u64_at(self.kpageflags.as_ref().unwrap(), pfn * 8)
+    }
+
+    /// Returns the number of times the page with frame number `pfn` is mapped.
+    pub fn page_map_count(&mut self, pfn: u64) -> io::Result<u64> {
+        if self.kpagecount.is_none() {
+            self.init_page_acct(false)?;
+        }
+        read_u64_at(self.kpagecount.as_ref().unwrap(), pfn * 8)
+    }
+
+    /// Marks the page idle and returns if the idle bit is still set after that.
+    pub fn is_page_idle(&mut self, pfn: u64) -> io::Result<bool> {
+        if self.pageidle.is_none() {
+            self.init_page_acct(true)?;
+        }
+        let pageidle = self.pageidle.as_ref().unwrap();
+        let offset = pfn_to_idle_bitmap_offset(pfn);
+        let idle_bit = 1u64 << (pfn % 64);
+
+        // set the bit corresponding to page frame
+        pageidle.write_all_at(&idle_bit.to_ne_bytes(), offset)?;
+        Ok(read_u64_at(pageidle, offset)? & idle_bit != 0)
+    }
+}
+
+fn pfn_to_idle_bitmap_offset(pfn: u64) -> u64 {
+    (pfn >> 6) << 3
+}
+
+fn read_u64_at(file: &File, offset: u64) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    file.read_exact_at(&mut buf, offset)?;
+    Ok(u64::from_ne_bytes(buf))
+}
+
+/// Reads `count` consecutive 64-bit entries of a pagemap-like file starting at entry `index`.
+pub(crate) fn read_u64_entries(file: &File, index: u64, count: usize) -> io::Result<Vec<u64>> {
+    let mut buf = vec![0u8; count * 8];
+    file.read_exact_at(&mut buf, index * 8)?;
+    Ok(buf.chunks_exact(8).map(|b| u64::from_ne_bytes(b.try_into().unwrap())).collect())
+}
+
+pub(crate) fn open_pagemap(pid: i32) -> io::Result<File> {
+    File::open(Path::new("/proc").join(pid.to_string()).join("pagemap"))
+}
+
+fn bits(x: u64, offset: u32, bits: u32) -> u64 {
+    (x >> offset) & ((1u64 << bits) - 1)
+}
+
+/// Returns if the page present bit is set in the value passed in.
+pub fn page_present(pagemap_val: u64) -> bool {
+    bits(pagemap_val, 63, 1) != 0
+}
+
+/// Returns if the page swapped bit is set in the value passed in.
+pub fn page_swapped(pagemap_val: u64) -> bool {
+    bits(pagemap_val, 62, 1) != 0
+}
+
+/// Returns the page frame number (physical page) from pagemap value.
+pub fn page_pfn(pagemap_val: u64) -> u64 {
+    bits(pagemap_val, 0, 55)
+}
+
+/// Returns the swap offset from a pagemap value of a swapped page.
+pub fn page_swap_offset(pagemap_val: u64) -> u64 {
+    bits(pagemap_val, 5, 50)
+}
+
+/// Returns the swap type from a pagemap value of a swapped page.
+pub fn page_swap_type(pagemap_val: u64) -> u64 {
+    bits(pagemap_val, 0, 5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pagemap_bits() {
+        let present = (1u64 << 63) | 0x1234;
+        assert!(page_present(present));
+        assert!(!page_swapped(present));
+        assert_eq!(page_pfn(present), 0x1234);
+
+        let swapped = (1u64 << 62) | (0x42 << 5) | 0x3;
+        assert!(!page_present(swapped));
+        assert!(page_swapped(swapped));
+        assert_eq!(page_swap_offset(swapped), 0x42);
+        assert_eq!(page_swap_type(swapped), 0x3);
+    }
+}
@@ -0,0 +1,745 @@
+// Copyright (C) 2026 The Android Open Source Project
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Per-process memory accounting, the Rust equivalent of `meminfo/procmeminfo.h`.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::meminfo::{MemUsage, Vma};
+use crate::page_size;
+use crate::pageacct::{
+    self, page_pfn, page_present, page_swap_offset, page_swapped, PageAcct, KPF_DIRTY,
+    KPF_REFERENCED,
+};
+use crate::sysmeminfo::parse_leading_u64;
+
+// List of VMA names that we don't want to process:
+//   - On ARM32, [vectors] is a special VMA that is outside of pagemap range.
+const BLOCKLISTED_VMAS: [&str; 1] = ["[vectors]"];
+
+// Additional VMA names skipped when reading /proc/<pid>/maps for pagemap based accounting:
+//   - On x86_64, [vsyscall] is outside of pagemap range as well, but smaps still reports it.
+const PAGEMAP_BLOCKLISTED_VMAS: [&str; 1] = ["[vsyscall]"];
+
+// Number of pagemap entries read at once while walking a vma.
+const MAX_PAGES_PER_READ: u64 = 2048;
+
+fn proc_path(pid: i32, file: &str) -> PathBuf {
+    Path::new("/proc").join(pid.to_string()).join(file)
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Memory accounting of a single process.
+///
+/// Each object reads /proc/<pid>/maps only once. This is done to make sure programs that are
+/// running for the lifetime of the system can recycle the objects and don't have to
+/// unnecessarily retain and update this object in memory.
+#[derive(Debug)]
+pub struct ProcMemInfo {
+    pid: i32,
+    get_wss: bool,
+    pgflags: u64,
+    pgflags_mask: u64,
+
+    maps: Vec<Vma>,
+    usage: MemUsage,
+    swap_offsets: Vec<u64>,
+    page_acct: PageAcct,
+}
+
+impl ProcMemInfo {
+    /// Reset the working set accounting of the process via /proc/<pid>/clear_refs.
+    pub fn reset_working_set(pid: i32) -> io::Result<()> {
+        fs::write(proc_path(pid, "clear_refs"), "1\n")
+    }
+
+    /// Creates the accounting object for `pid`. If `get_wss` is set, the object only reports
+    /// the working set of the process. Only pages whose kpageflags masked with `pgflags_mask`
+    /// are equal to `pgflags` are counted.
+    pub fn new(pid: i32, get_wss: bool, pgflags: u64, pgflags_mask: u64) -> Self {
+        Self {
+            pid,
+            get_wss,
+            pgflags,
+            pgflags_mask,
+            maps: Vec::new(),
+            usage: MemUsage::default(),
+            swap_offsets: Vec::new(),
+            page_acct: PageAcct::new(),
+        }
+    }
+
+    pub fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    /// Returns all mappings of the process with their usage stats computed from pagemap.
+    pub fn maps(&mut self) -> io::Result<&[Vma]> {
+        self.read_maps(false, true)?;
+        Ok(&self.maps)
+    }
+
+    /// Same as `maps()` except, only valid for reading working set using
+    /// CONFIG_IDLE_PAGE_TRACKING support in kernel.
+    pub fn maps_with_page_idle(&mut self) -> io::Result<&[Vma]> {
+        self.read_maps(true, true)?;
+        Ok(&self.maps)
+    }
+
+    /// Same as `maps()` except, do not read the usage stats for each map.
+    pub fn maps_without_usage_stats(&mut self) -> io::Result<&[Vma]> {
+        self.read_maps(false, false)?;
+        Ok(&self.maps)
+    }
+
+    /// If `maps_without_usage_stats()` was called, this function will fill in usage stats for
+    /// this single vma.
+    pub fn fill_in_vma_stats(&mut self, vma: &mut Vma) -> io::Result<()> {
+        let pagemap = pageacct::open_pagemap(self.pid)?;
+        let mut stats = VmaStats::new(self, &pagemap, false);
+        stats.read(vma)
+    }
+
+    /// Returns the memory usage of the process. Fails if the object was created for reading
+    /// the working set.
+    pub fn usage(&mut self) -> io::Result<&MemUsage> {
+        if self.get_wss {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Trying to read process memory usage for {} using invalid object",
+                    self.pid
+                ),
+            ));
+        }
+        self.read_maps(false, true)?;
+        Ok(&self.usage)
+    }
+
+    /// Returns the working set of the process. Fails if the object was not created for reading
+    /// the working set.
+    pub fn wss(&mut self) -> io::Result<&MemUsage> {
+        if !self.get_wss {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Trying to read process working set for {} using invalid object", self.pid),
+            ));
+        }
+        self.read_maps(false, true)?;
+        Ok(&self.usage)
+    }
+
+    /// Returns the swap offsets of all swapped out pages of the process.
+    pub fn swap_offsets(&mut self) -> io::Result<&[u64]> {
+        if self.get_wss {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Trying to read process swap offsets for {} using invalid object",
+                    self.pid
+                ),
+            ));
+        }
+        self.read_maps(false, true)?;
+        Ok(&self.swap_offsets)
+    }
+
+    /// Collect all vmas from /proc/<pid>/smaps, or from `path` if given. Each `Vma` is *fully*
+    /// populated by this method (unlike `smaps_or_rollup()`).
+    pub fn smaps(&mut self, path: Option<&Path>) -> io::Result<&[Vma]> {
+        if !self.maps.is_empty() {
+            return Ok(&self.maps);
+        }
+
+        let path = path.map_or_else(|| proc_path(self.pid, "smaps"), Path::to_path_buf);
+        let mut maps = Vec::new();
+        for_each_vma_from_file(&path, |vma| {
+            if !BLOCKLISTED_VMAS.contains(&vma.name.as_str()) {
+                maps.push(vma.clone());
+            }
+        })?;
+        self.maps = maps;
+        Ok(&self.maps)
+    }
+
+    /// Reads /proc/<pid>/smaps and calls `callback` for each vma found.
+    pub fn for_each_vma(&self, callback: impl FnMut(&Vma)) -> io::Result<()> {
+        for_each_vma_from_file(proc_path(self.pid, "smaps"), callback)
+    }
+
+    /// Parses either of /proc/<pid>/{smaps, smaps_rollup} and returns the process's Pss,
+    /// Rss, Uss, private_clean, private_dirty and SwapPss. All other fields are zeroed.
+    pub fn smaps_or_rollup(&self) -> io::Result<MemUsage> {
+        smaps_or_rollup_from_file(self.smaps_or_rollup_path())
+    }
+
+    /// Parses either of /proc/<pid>/{smaps, smaps_rollup} and returns the process's Pss.
+    pub fn smaps_or_rollup_pss(&self) -> io::Result<u64> {
+        smaps_or_rollup_pss_from_file(self.smaps_or_rollup_path())
+    }
+
+    /// Returns the numeric fields of /proc/<pid>/status, e.g. "VmRSS" or "VmSwap", by name.
+    pub fn status(&self) -> io::Result<HashMap<String, u64>> {
+        read_status_from_file(proc_path(self.pid, "status"))
+    }
+
+    /// Reads /proc/<pid>/pagemap for each page within `vma`. It is assumed that `vma` is
+    /// obtained from this object, no special checks are made to see if it is *valid*.
+    pub fn page_map(&self, vma: &Vma) -> io::Result<Vec<u64>> {
+        let pagemap = pageacct::open_pagemap(self.pid)?;
+        let pagesz = page_size();
+        let nr_pages = ((vma.end - vma.start) / pagesz) as usize;
+        pageacct::read_u64_entries(&pagemap, vma.start / pagesz, nr_pages)
+    }
+
+    fn smaps_or_rollup_path(&self) -> PathBuf {
+        let file = if is_smaps_rollup_supported(self.pid) { "smaps_rollup" } else { "smaps" };
+        proc_path(self.pid, file)
+    }
+
+    fn read_maps(&mut self, use_pageidle: bool, get_usage_stats: bool) -> io::Result<()> {
+        if !self.maps.is_empty() {
+            return Ok(());
+        }
+
+        let maps_file = proc_path(self.pid, "maps");
+        let content = fs::read_to_string(&maps_file)?;
+        let mut maps = Vec::new();
+        for line in content.lines() {
+            let vma = Vma::from_maps_line(line)
+                .ok_or_else(|| invalid_data(format!("Failed to parse {}", maps_file.display())))?;
+            let name = vma.name.as_str();
+            if !BLOCKLISTED_VMAS.contains(&name) && !PAGEMAP_BLOCKLISTED_VMAS.contains(&name) {
+                maps.push(vma);
+            }
+        }
+
+        if get_usage_stats {
+            let pagemap = pageacct::open_pagemap(self.pid)?;
+            let mut stats = VmaStats::new(self, &pagemap, use_pageidle);
+            for vma in maps.iter_mut() {
+                stats.read(vma)?;
+            }
+            for vma in &maps {
+                self.usage.add(&vma.usage);
+            }
+        }
+
+        self.maps = maps;
+        Ok(())
+    }
+}
+
+// Walks the pagemap of a vma and accounts each page in the vma's usage.
+struct VmaStats<'a> {
+    pagemap: &'a File,
+    page_acct: &'a mut PageAcct,
+    swap_offsets: &'a mut Vec<u64>,
+    get_wss: bool,
+    use_pageidle: bool,
+    pgflags: u64,
+    pgflags_mask: u64,
+}
+
+impl<'a> VmaStats<'a> {
+    fn new(pmi: &'a mut ProcMemInfo, pagemap: &'a File, use_pageidle: bool) -> Self {
+        Self {
+            pagemap,
+            page_acct: &mut pmi.page_acct,
+            swap_offsets: &mut pmi.swap_offsets,
+            get_wss: pmi.get_wss,
+            use_pageidle,
+            pgflags: pmi.pgflags,
+            pgflags_mask: pmi.pgflags_mask,
+        }
+    }
+
+    fn read(&mut self, vma: &mut Vma) -> io::Result<()> {
+        if self.get_wss && self.use_pageidle {
+            self.page_acct.init_page_acct(true)?;
+        }
+
+        let pagesz = page_size();
+        let first_page = vma.start / pagesz;
+        let last_page = vma.end / pagesz;
+        let mut cur_page = first_page;
+        while cur_page < last_page {
+            let count = (last_page - cur_page).min(MAX_PAGES_PER_READ);
+            let entries = pageacct::read_u64_entries(self.pagemap, cur_page, count as usize)
+                .map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!(
+                            "Failed to read page data at offset {:#x} for vma {}: {}",
+                            cur_page * 8,
+                            vma.name,
+                            e
+                        ),
+                    )
+                })?;
+            for page_info in entries {
+                self.account_page(vma, page_info, pagesz)?;
+            }
+            cur_page += count;
+        }
+        Ok(())
+    }
+
+    fn account_page(&mut self, vma: &mut Vma, page_info: u64, pagesz: u64) -> io::Result<()> {
+        if !self.get_wss {
+            vma.usage.vss += pagesz;
+        }
+
+        if !page_present(page_info) && !page_swapped(page_info) {
+            return Ok(());
+        }
+
+        if page_swapped(page_info) {
+            vma.usage.swap += pagesz;
+            self.swap_offsets.push(page_swap_offset(page_info));
+            return Ok(());
+        }
+
+        let page_frame = page_pfn(page_info);
+        let cur_page_flags = self.page_acct.page_flags(page_frame).inspect_err(|_| {
+            self.swap_offsets.clear();
+        })?;
+
+        // skip unwanted pages from the count
+        if (cur_page_flags & self.pgflags_mask) != self.pgflags {
+            return Ok(());
+        }
+
+        let cur_page_counts = self.page_acct.page_map_count(page_frame).inspect_err(|_| {
+            self.swap_offsets.clear();
+        })?;
+
+        // Page was unmapped between the presence check at the beginning of the loop and here.
+        if cur_page_counts == 0 {
+            return Ok(());
+        }
+
+        let is_dirty = cur_page_flags & (1 << KPF_DIRTY) != 0;
+        let is_private = cur_page_counts == 1;
+        // Working set
+        if self.get_wss {
+            let is_referenced = if self.use_pageidle {
+                self.page_acct.is_page_idle(page_frame)?
+            } else {
+                cur_page_flags & (1 << KPF_REFERENCED) != 0
+            };
+            if !is_referenced {
+                return Ok(());
+            }
+            // This effectively makes vss = rss for the working set is requested.
+            vma.usage.vss += pagesz;
+        }
+
+        vma.usage.rss += pagesz;
+        vma.usage.uss += if is_private { pagesz } else { 0 };
+        vma.usage.pss += pagesz / cur_page_counts;
+        match (is_private, is_dirty) {
+            (true, true) => vma.usage.private_dirty += pagesz,
+            (true, false) => vma.usage.private_clean += pagesz,
+            (false, true) => vma.usage.shared_dirty += pagesz,
+            (false, false) => vma.usage.shared_clean += pagesz,
+        }
+        Ok(())
+    }
+}
+
+// Returns true if the line was valid smaps stats line false otherwise.
+fn parse_smaps_field(line: &str, stats: &mut MemUsage) -> bool {
+    let Some((field, value)) = line.split_once(char::is_whitespace) else {
+        return false;
+    };
+    if !field.ends_with(':') {
+        return false;
+    }
+
+    let val = || parse_leading_u64(value).unwrap_or(0);
+    match field {
+        "Size:" => stats.vss = val(),
+        "Rss:" => stats.rss = val(),
+        "Pss:" => stats.pss = val(),
+        "Shared_Clean:" => stats.shared_clean = val(),
+        "Shared_Dirty:" => stats.shared_dirty = val(),
+        "Private_Clean:" => {
+            stats.private_clean = val();
+            stats.uss += stats.private_clean;
+        }
+        "Private_Dirty:" => {
+            stats.private_dirty = val();
+            stats.uss += stats.private_dirty;
+        }
+        "Swap:" => stats.swap = val(),
+        "SwapPss:" => stats.swap_pss = val(),
+        _ => {}
+    }
+    true
+}
+
+/// Makes callback for each vma found in the file provided. The file is expected to be in the
+/// same format as /proc/<pid>/smaps.
+pub fn for_each_vma_from_file<P: AsRef<Path>>(
+    path: P,
+    mut callback: impl FnMut(&Vma),
+) -> io::Result<()> {
+    let path = path.as_ref();
+    let reader = BufReader::new(File::open(path)?);
+    let mut vma: Option<Vma> = None;
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(cur) = vma.as_mut() {
+            if parse_smaps_field(&line, &mut cur.usage) {
+                // This was a stats field
+                continue;
+            }
+            // Done collecting stats, make the call back
+            callback(cur);
+        }
+
+        vma = Some(
+            Vma::from_maps_line(&line)
+                .ok_or_else(|| invalid_data(format!("Failed to parse {}", path.display())))?,
+        );
+    }
+
+    if let Some(cur) = vma {
+        callback(&cur);
+    }
+    Ok(())
+}
+
+const ROLLUP_UNTRIED: u8 = 0;
+const ROLLUP_SUPPORTED: u8 = 1;
+const ROLLUP_UNSUPPORTED: u8 = 2;
+
+static ROLLUP_SUPPORT: AtomicU8 = AtomicU8::new(ROLLUP_UNTRIED);
+
+/// Returns if the kernel supports /proc/<pid>/smaps_rollup. The result of the first call is
+/// cached and returned by every later call regardless of `pid`.
+pub fn is_smaps_rollup_supported(pid: i32) -> bool {
+    match ROLLUP_SUPPORT.load(Ordering::Relaxed) {
+        ROLLUP_SUPPORTED => return true,
+        ROLLUP_UNSUPPORTED => return false,
+        _ => {}
+    }
+
+    // The caller MUST fallback to using /proc/<pid>/smaps if this fails for any reason.
+    let supported = File::open(proc_path(pid, "smaps_rollup")).is_ok();
+    ROLLUP_SUPPORT
+        .store(if supported { ROLLUP_SUPPORTED } else { ROLLUP_UNSUPPORTED }, Ordering::Relaxed);
+    supported
+}
+
+/// Same as `ProcMemInfo::smaps_or_rollup()` but reads the statistics directly from a file. The
+/// file MUST be in the same format as /proc/<pid>/smaps or /proc/<pid>/smaps_rollup.
+pub fn smaps_or_rollup_from_file<P: AsRef<Path>>(path: P) -> io::Result<MemUsage> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut stats = MemUsage::default();
+    for line in reader.lines() {
+        let line = line?;
+        let Some((field, value)) = line.split_once(char::is_whitespace) else {
+            continue;
+        };
+        let val = || parse_leading_u64(value).unwrap_or(0);
+        match field {
+            "Pss:" => stats.pss += val(),
+            "Rss:" => stats.rss += val(),
+            "SwapPss:" => stats.swap_pss += val(),
+            "Private_Clean:" => {
+                let prcl = val();
+                stats.private_clean += prcl;
+                stats.uss += prcl;
+            }
+            "Private_Dirty:" => {
+                let prdi = val();
+                stats.private_dirty += prdi;
+                stats.uss += prdi;
+            }
+            _ => {}
+        }
+    }
+    Ok(stats)
+}
+
+/// Same as `ProcMemInfo::smaps_or_rollup_pss()` but reads the statistics directly from a file
+/// and returns total Pss in kB. The file MUST be in the same format as /proc/<pid>/smaps or
+/// /proc/<pid>/smaps_rollup.
+pub fn smaps_or_rollup_pss_from_file<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut pss = 0;
+    for line in reader.lines() {
+        if let Some(value) = line?.strip_prefix("Pss:") {
+            pss += parse_leading_u64(value).unwrap_or(0);
+        }
+    }
+    Ok(pss)
+}
+
+/// Reads a file in the /proc/<pid>/status format and returns the first number of every field
+/// that starts with one, by field name without the trailing ':'.
+pub fn read_status_from_file<P: AsRef<Path>>(path: P) -> io::Result<HashMap<String, u64>> {
+    let content = fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .filter_map(|line| {
+            let (field, value) = line.split_once(':')?;
+            Some((field.to_string(), parse_leading_u64(value)?))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{testdata_dir, write_temp_file};
+    use std::process;
+
+    const SMAPS: &str = "12c00000-13440000 rw-p 00000000 00:00 0                                  [anon:dalvik-main space (region space)]
+Name:           [anon:dalvik-main space (region space)]
+Size:               8448 kB
+KernelPageSize:        4 kB
+MMUPageSize:           4 kB
+Rss:                2652 kB
+Pss:                2652 kB
+Shared_Clean:        840 kB
+Shared_Dirty:         40 kB
+Private_Clean:        84 kB
+Private_Dirty:      2652 kB
+Referenced:         2652 kB
+Anonymous:          2652 kB
+AnonHugePages:         0 kB
+ShmemPmdMapped:        0 kB
+Shared_Hugetlb:        0 kB
+Private_Hugetlb:       0 kB
+Swap:                102 kB
+SwapPss:              70 kB
+Locked:             2652 kB
+VmFlags: rd wr mr mw me ac
+";
+
+    fn pid() -> i32 {
+        process::id() as i32
+    }
+
+    #[test]
+    fn test_working_set_reset() {
+        assert!(ProcMemInfo::reset_working_set(pid()).is_ok());
+    }
+
+    #[test]
+    fn test_usage_empty() {
+        // If we created the object for getting working set, the usage must not be available.
+        let mut proc_mem = ProcMemInfo::new(pid(), true, 0, 0);
+        assert!(proc_mem.usage().is_err());
+        assert!(proc_mem.swap_offsets().is_err());
+    }
+
+    #[test]
+    fn test_wss_empty() {
+        let mut proc_mem = ProcMemInfo::new(pid(), false, 0, 0);
+        assert!(proc_mem.wss().is_err());
+    }
+
+    #[test]
+    fn test_maps_usage_not_empty() {
+        let mut proc_mem = ProcMemInfo::new(pid(), false, 0, 0);
+        let maps = proc_mem.maps().unwrap();
+        assert!(!maps.is_empty());
+        let mut total = MemUsage::default();
+        for map in maps {
+            assert_ne!(map.usage.vss, 0);
+            total.add(&map.usage);
+        }
+
+        // Crude check that stats are actually being read.
+        assert_ne!(total.rss, 0, "RSS zero for all maps, that is not possible.");
+        assert_ne!(total.pss, 0, "PSS zero for all maps, that is not possible.");
+        assert_ne!(total.uss, 0, "USS zero for all maps, that is not possible.");
+        assert_eq!(proc_mem.usage().unwrap().vss, total.vss);
+    }
+
+    #[test]
+    fn test_maps_usage_fill_in_later() {
+        let mut proc_mem = ProcMemInfo::new(pid(), false, 0, 0);
+        let maps = proc_mem.maps_without_usage_stats().unwrap().to_vec();
+        assert!(!maps.is_empty());
+        for map in maps {
+            assert_eq!(map.usage, MemUsage::default());
+            let mut update_map = map.clone();
+            proc_mem.fill_in_vma_stats(&mut update_map).unwrap();
+            assert_eq!(map.start, update_map.start);
+            assert_eq!(map.name, update_map.name);
+            // Check that at least one usage stat was updated.
+            assert_ne!(update_map.usage.vss, 0);
+        }
+    }
+
+    #[test]
+    fn test_page_map() {
+        let mut proc_mem = ProcMemInfo::new(pid(), false, 0, 0);
+        let vma = proc_mem.maps_without_usage_stats().unwrap()[0].clone();
+        let pagemap = proc_mem.page_map(&vma).unwrap();
+        assert_eq!(pagemap.len() as u64, (vma.end - vma.start) / page_size());
+    }
+
+    #[test]
+    fn test_is_smaps_supported() {
+        let supported = is_smaps_rollup_supported(pid());
+        assert_eq!(proc_path(pid(), "smaps_rollup").exists(), supported);
+        // Second call must return what the first one returned regardless of the pid parameter.
+        assert_eq!(is_smaps_rollup_supported(-1), supported);
+    }
+
+    #[test]
+    fn test_smaps_or_rollup() {
+        let rollup =
+            "12c00000-7fe859e000 ---p 00000000 00:00 0                                [rollup]
+Rss:              331908 kB
+Pss:              202052 kB
+Shared_Clean:     158492 kB
+Shared_Dirty:      18928 kB
+Private_Clean:     90472 kB
+Private_Dirty:     64016 kB
+Referenced:       318700 kB
+Anonymous:         81984 kB
+AnonHugePages:         0 kB
+Shared_Hugetlb:        0 kB
+Private_Hugetlb:       0 kB
+Swap:               5344 kB
+SwapPss:             442 kB
+Locked:          1523537 kB";
+        let path = write_temp_file("smaps_rollup", rollup);
+        let stats = smaps_or_rollup_from_file(&path).unwrap();
+        assert_eq!(stats.rss, 331908);
+        assert_eq!(stats.pss, 202052);
+        assert_eq!(stats.uss, 154488);
+        assert_eq!(stats.private_clean, 90472);
+        assert_eq!(stats.private_dirty, 64016);
+        assert_eq!(stats.swap_pss, 442);
+    }
+
+    #[test]
+    fn test_smaps_or_rollup_smaps() {
+        let path = write_temp_file("smaps_or_rollup_smaps", SMAPS);
+        let stats = smaps_or_rollup_from_file(&path).unwrap();
+        assert_eq!(stats.rss, 2652);
+        assert_eq!(stats.pss, 2652);
+        assert_eq!(stats.uss, 2736);
+        assert_eq!(stats.private_clean, 84);
+        assert_eq!(stats.private_dirty, 2652);
+        assert_eq!(stats.swap_pss, 70);
+    }
+
+    #[test]
+    fn test_smaps_or_rollup_pss() {
+        let path = write_temp_file("smaps_or_rollup_pss", SMAPS);
+        assert_eq!(smaps_or_rollup_pss_from_file(&path).unwrap(), 2652);
+        assert_eq!(
+            smaps_or_rollup_pss_from_file(testdata_dir("testdata1/smaps_short")).unwrap(),
+            19119
+        );
+    }
+
+    #[test]
+    fn test_smaps_or_rollup_live() {
+        let proc_mem = ProcMemInfo::new(pid(), false, 0, 0);
+        let stats = proc_mem.smaps_or_rollup().unwrap();
+        assert_ne!(stats.pss, 0);
+        assert_eq!(proc_mem.smaps_or_rollup_pss().unwrap(), stats.pss);
+    }
+
+    fn check_smaps_short(vmas: &[Vma]) {
+        // We should get a total of 6 vmas
+        assert_eq!(vmas.len(), 6);
+
+        let vss: Vec<u64> = vmas.iter().map(|v| v.usage.vss).collect();
+        assert_eq!(vss, [32768, 11204, 16896, 260, 6060, 4]);
+
+        assert_eq!(vmas[0].name, "[anon:dalvik-zygote-jit-code-cache]");
+        assert_eq!(vmas[1].name, "/system/framework/x86_64/boot-framework.art");
+        assert!(
+            vmas[2].name == "[anon:libc_malloc]" || vmas[2].name.starts_with("[anon:scudo:"),
+            "Unknown map name {}",
+            vmas[2].name
+        );
+        assert_eq!(
+            vmas[3].name,
+            "/system/priv-app/SettingsProvider/oat/x86_64/SettingsProvider.odex"
+        );
+        assert_eq!(vmas[4].name, "/system/lib64/libhwui.so");
+        assert_eq!(vmas[5].name, "[vsyscall]");
+
+        let field = |f: fn(&MemUsage) -> u64| vmas.iter().map(|v| f(&v.usage)).collect::<Vec<_>>();
+        assert_eq!(field(|u| u.rss), [2048, 11188, 15272, 260, 4132, 0]);
+        assert_eq!(field(|u| u.pss), [113, 2200, 15272, 260, 1274, 0]);
+        assert_eq!(field(|u| u.uss), [0, 1660, 15272, 260, 0, 0]);
+        assert_eq!(field(|u| u.private_clean), [0, 0, 0, 260, 0, 0]);
+        assert_eq!(field(|u| u.private_dirty), [0, 1660, 15272, 0, 0, 0]);
+        assert_eq!(field(|u| u.shared_clean), [0, 80, 0, 0, 4132, 0]);
+        assert_eq!(field(|u| u.shared_dirty), [2048, 9448, 0, 0, 0, 0]);
+        assert_eq!(field(|u| u.swap), [0; 6]);
+        assert_eq!(field(|u| u.swap_pss), [0; 6]);
+    }
+
+    #[test]
+    fn test_for_each_vma_from_file() {
+        let mut vmas = Vec::new();
+        for_each_vma_from_file(testdata_dir("testdata1/smaps_short"), |v| vmas.push(v.clone()))
+            .unwrap();
+        check_smaps_short(&vmas);
+    }
+
+    #[test]
+    fn test_smaps() {
+        let mut proc_mem = ProcMemInfo::new(pid(), false, 0, 0);
+        let path = testdata_dir("testdata1/smaps_short");
+        check_smaps_short(proc_mem.smaps(Some(&path)).unwrap());
+    }
+
+    #[test]
+    fn test_smaps_return() {
+        // Make sure smaps() is never empty for any process
+        let mut proc_mem = ProcMemInfo::new(pid(), false, 0, 0);
+        assert!(!proc_mem.smaps(None).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_read_status() {
+        let status = "Name:\tsurfaceflinger
+Pid:\t523
+VmPeak:\t 2248608 kB
+VmRSS:\t   61812 kB
+VmSwap:\t     924 kB
+";
+        let path = write_temp_file("status", status);
+        let fields = read_status_from_file(&path).unwrap();
+        assert!(!fields.contains_key("Name"));
+        assert_eq!(fields["Pid"], 523);
+        assert_eq!(fields["VmRSS"], 61812);
+        assert_eq!(fields["VmSwap"], 924);
+
+        let proc_mem = ProcMemInfo::new(pid(), false, 0, 0);
+        assert_eq!(proc_mem.status().unwrap()["Pid"], pid() as u64);
+    }
+}
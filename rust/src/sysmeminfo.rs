@@ -0,0 +1,507 @@
+// Copyright (C) 2026 The Android Open Source Project
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! System or global memory accounting, the Rust equivalent of `meminfo/sysmeminfo.h`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::page_size;
+
+/// Default location of the system memory statistics.
+pub const MEMINFO_PATH: &str = "/proc/meminfo";
+/// Default location of the kernel vmalloc area description.
+pub const VMALLOCINFO_PATH: &str = "/proc/vmallocinfo";
+/// Default location of the virtual memory statistics.
+pub const VMSTAT_PATH: &str = "/proc/vmstat";
+/// Default location of the total ION heaps allocation size.
+pub const ION_HEAPS_PATH: &str = "/sys/kernel/ion/total_heaps_kb";
+/// Default location of the total ION pools allocation size.
+pub const ION_POOLS_PATH: &str = "/sys/kernel/ion/total_pools_kb";
+/// Default location of the per-buffer dmabuf sysfs statistics.
+pub const DMABUF_SYSFS_STATS_PATH: &str = "/sys/kernel/dmabuf/buffers";
+
+// Special tag that is resolved from the zram devices instead of /proc/meminfo, so that
+// android_os_Debug and friends can look it up along with the rest of the numbers.
+const ZRAM_TAG: &str = "Zram:";
+
+// We assume zram devices appear in range 0-255 and appear always in sequence under /sys/block.
+const MAX_ZRAM_DEVICES: u32 = 256;
+
+/// Parsed subset of /proc/meminfo. All values are in kB.
+#[derive(Debug, Default, Clone)]
+pub struct SysMemInfo {
+    mem_in_kb: HashMap<&'static str, u64>,
+}
+
+impl SysMemInfo {
+    pub const MEM_TOTAL: &'static str = "MemTotal:";
+    pub const MEM_FREE: &'static str = "MemFree:";
+    pub const MEM_BUFFERS: &'static str = "Buffers:";
+    pub const MEM_CACHED: &'static str = "Cached:";
+    pub const MEM_SHMEM: &'static str = "Shmem:";
+    pub const MEM_SLAB: &'static str = "Slab:";
+    pub const MEM_SRECLAIM: &'static str = "SReclaimable:";
+    pub const MEM_SUNRECLAIM: &'static str = "SUnreclaim:";
+    pub const MEM_SWAP_TOTAL: &'static str = "SwapTotal:";
+    pub const MEM_SWAP_FREE: &'static str = "SwapFree:";
+    pub const MEM_MAPPED: &'static str = "Mapped:";
+    pub const MEM_VMALLOC_USED: &'static str = "VmallocUsed:";
+    pub const MEM_PAGE_TABLES: &'static str = "PageTables:";
+    pub const MEM_KERNEL_STACK: &'static str = "KernelStack:";
+    pub const MEM_KRECLAIMABLE: &'static str = "KReclaimable:";
+    /// Not part of the default tags, read them with [`SysMemInfo::read_mem_info_tags()`].
+    pub const MEM_UNEVICTABLE: &'static str = "Unevictable:";
+    pub const MEM_MLOCKED: &'static str = "Mlocked:";
+
+    pub const DEFAULT_SYS_MEMINFO_TAGS: [&'static str; 15] = [
+        Self::MEM_TOTAL,
+        Self::MEM_FREE,
+        Self::MEM_BUFFERS,
+        Self::MEM_CACHED,
+        Self::MEM_SHMEM,
+        Self::MEM_SLAB,
+        Self::MEM_SRECLAIM,
+        Self::MEM_SUNRECLAIM,
+        Self::MEM_SWAP_TOTAL,
+        Self::MEM_SWAP_FREE,
+        Self::MEM_MAPPED,
+        Self::MEM_VMALLOC_USED,
+        Self::MEM_PAGE_TABLES,
+        Self::MEM_KERNEL_STACK,
+        Self::MEM_KRECLAIMABLE,
+    ];
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse /proc/meminfo and read values that are needed.
+    pub fn read_mem_info(&mut self) -> io::Result<()> {
+        self.read_mem_info_from(MEMINFO_PATH)
+    }
+
+    /// Same as `read_mem_info()`, but reads from a file in the /proc/meminfo format.
+    pub fn read_mem_info_from<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let tags = &Self::DEFAULT_SYS_MEMINFO_TAGS;
+        parse_mem_info(path.as_ref(), tags, |index, val| {
+            self.mem_in_kb.insert(tags[index], val);
+        })
+    }
+
+    /// Reads the values of `tags` from `path` and returns them in the same order as the tags.
+    /// Tags missing from the file are reported as 0. The special "Zram:" tag is filled in with
+    /// the total zram usage from `mem_zram_kb()`.
+    pub fn read_mem_info_tags<P: AsRef<Path>>(
+        &self,
+        tags: &[&str],
+        path: P,
+    ) -> io::Result<Vec<u64>> {
+        let mut out = vec![0; tags.len()];
+        if let Some(index) = tags.iter().position(|tag| *tag == ZRAM_TAG) {
+            out[index] = self.mem_zram_kb(None);
+        }
+        parse_mem_info(path.as_ref(), tags, |index, val| out[index] = val)?;
+        Ok(out)
+    }
+
+    /// Same as [`read_vmalloc_info()`] for /proc/vmallocinfo.
+    pub fn read_vmalloc_info(&self) -> u64 {
+        read_vmalloc_info(VMALLOCINFO_PATH)
+    }
+
+    // getters
+    pub fn mem_total_kb(&self) -> u64 {
+        self.get(Self::MEM_TOTAL)
+    }
+    pub fn mem_free_kb(&self) -> u64 {
+        self.get(Self::MEM_FREE)
+    }
+    pub fn mem_buffers_kb(&self) -> u64 {
+        self.get(Self::MEM_BUFFERS)
+    }
+    pub fn mem_cached_kb(&self) -> u64 {
+        self.get(Self::MEM_CACHED)
+    }
+    pub fn mem_shmem_kb(&self) -> u64 {
+        self.get(Self::MEM_SHMEM)
+    }
+    pub fn mem_slab_kb(&self) -> u64 {
+        self.get(Self::MEM_SLAB)
+    }
+    pub fn mem_slab_reclaimable_kb(&self) -> u64 {
+        self.get(Self::MEM_SRECLAIM)
+    }
+    pub fn mem_slab_unreclaimable_kb(&self) -> u64 {
+        self.get(Self::MEM_SUNRECLAIM)
+    }
+    pub fn mem_swap_kb(&self) -> u64 {
+        self.get(Self::MEM_SWAP_TOTAL)
+    }
+    pub fn mem_swap_free_kb(&self) -> u64 {
+        self.get(Self::MEM_SWAP_FREE)
+    }
+    pub fn mem_mapped_kb(&self) -> u64 {
+        self.get(Self::MEM_MAPPED)
+    }
+    pub fn mem_vmalloc_used_kb(&self) -> u64 {
+        self.get(Self::MEM_VMALLOC_USED)
+    }
+    pub fn mem_page_tables_kb(&self) -> u64 {
+        self.get(Self::MEM_PAGE_TABLES)
+    }
+    pub fn mem_kernel_stack_kb(&self) -> u64 {
+        self.get(Self::MEM_KERNEL_STACK)
+    }
+    pub fn mem_kreclaimable_kb(&self) -> u64 {
+        self.get(Self::MEM_KRECLAIMABLE)
+    }
+
+    /// Returns the physical memory used by zram in kB. If `zram_dev` is `None`, the usage of
+    /// all zram devices under /sys/block is added up. Returns 0 if any device can't be read.
+    pub fn mem_zram_kb(&self, zram_dev: Option<&Path>) -> u64 {
+        if let Some(dev) = zram_dev {
+            return mem_zram_device(dev).map_or(0, |total| total / 1024);
+        }
+
+        let mut mem_zram_total = 0;
+        for i in 0..MAX_ZRAM_DEVICES {
+            let dev = PathBuf::from(format!("/sys/block/zram{}/", i));
+            if !dev.exists() {
+                // Stop looking for zram devices once we find one is missing.
+                break;
+            }
+            match mem_zram_device(&dev) {
+                Ok(used) => mem_zram_total += used,
+                Err(_) => return 0,
+            }
+        }
+
+        mem_zram_total / 1024
+    }
+
+    fn get(&self, tag: &str) -> u64 {
+        self.mem_in_kb.get(tag).copied().unwrap_or(0)
+    }
+}
+
+// Reads each line of 'path' that starts with one of 'tags' and calls 'store_val' with the index
+// of the tag and the value found on that line.
+fn parse_mem_info(
+    path: &Path,
+    tags: &[&str],
+    mut store_val: impl FnMut(usize, u64),
+) -> io::Result<()> {
+    let content = fs::read_to_string(path)?;
+    for (lineno, line) in content.lines().enumerate() {
+        let Some((index, tag)) =
+            tags.iter().enumerate().find(|(_, tag)| **tag != ZRAM_TAG && line.starts_with(**tag))
+        else {
+            continue;
+        };
+        let val = parse_leading_u64(&line[tag.len()..]).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Failed to parse line: {} in file: {}", lineno + 1, path.display()),
+            )
+        })?;
+        store_val(index, val);
+    }
+    Ok(())
+}
+
+// Parses the unsigned number at the start of 's', ignoring leading whitespace and anything after
+// the digits (e.g. the " kB" suffix).
+pub(crate) fn parse_leading_u64(s: &str) -> Option<u64> {
+    let s = s.trim_start();
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s[..end].parse().ok()
+}
+
+// Returns the physical memory used by a single zram device in bytes.
+fn mem_zram_device(zram_dev: &Path) -> io::Result<u64> {
+    // Only if we do have mm_stat, use it. Otherwise, fall through to trying out the old
+    // 'mem_used_total'.
+    if let Ok(mmstat) = fs::read_to_string(zram_dev.join("mm_stat")) {
+        return mmstat.split_whitespace().nth(2).and_then(|v| v.parse().ok()).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Malformed mm_stat file in: {}", zram_dev.display()),
+            )
+        });
+    }
+
+    read_sysfs_file(&zram_dev.join("mem_used_total"))
+}
+
+/// Parse /proc/vmallocinfo and return total physical memory mapped in vmalloc area by the
+/// kernel. Note that this deliberately ignores binder buffers. They are _always_ mapped in a
+/// process and are counted for in each process.
+pub fn read_vmalloc_info<P: AsRef<Path>>(path: P) -> u64 {
+    let Ok(content) = fs::read_to_string(path) else {
+        return 0;
+    };
+
+    // We are looking for lines like
+    //
+    // 0x0000000000000000-0x0000000000000000   12288 drm_property_create_blob+0x44/0xec pages=2 vmalloc
+    // 0x0000000000000000-0x0000000000000000    8192 wlan_logging_sock_init_svc+0xf8/0x4f0 [wlan] pages=1 vmalloc
+    //
+    // Notice that if the caller is coming from a module, the kernel prints and extra
+    // "[module_name]" after the address and the symbol of the call site, so the number of pages
+    // isn't at a fixed column.
+    content
+        .lines()
+        .filter_map(|line| line.find("pages=").map(|start| &line[start + "pages=".len()..]))
+        .filter_map(parse_leading_u64)
+        .map(|nr_pages| nr_pages * page_size())
+        .sum()
+}
+
+/// Parse /proc/vmstat and return all counters by name.
+pub fn read_vmstat<P: AsRef<Path>>(path: P) -> io::Result<HashMap<String, u64>> {
+    let content = fs::read_to_string(path.as_ref())?;
+    content
+        .lines()
+        .map(|line| {
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next().and_then(|v| v.parse().ok())) {
+                (Some(name), Some(val)) => Ok((name.to_string(), val)),
+                _ => Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Malformed line '{}' in file: {}", line, path.as_ref().display()),
+                )),
+            }
+        })
+        .collect()
+}
+
+fn read_sysfs_file(path: &Path) -> io::Result<u64> {
+    let content = fs::read_to_string(path)?;
+    content.trim().parse().map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Invalid file format: {}", path.display()),
+        )
+    })
+}
+
+/// Read ION heaps allocation size in kB.
+pub fn read_ion_heaps_size_kb<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+    read_sysfs_file(path.as_ref())
+}
+
+/// Read ION pools allocation size in kB.
+pub fn read_ion_pools_size_kb<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+    read_sysfs_file(path.as_ref())
+}
+
+/// Read the total size of all exported dmabufs in kB from the dmabuf sysfs statistics, where
+/// each buffer has its own directory containing a 'size' file in bytes.
+pub fn read_dmabuf_total_exported_kb<P: AsRef<Path>>(path: P) -> io::Result<u64> {
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        total += read_sysfs_file(&entry?.path().join("size"))?;
+    }
+    Ok(total / 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{testdata_dir, write_temp_file, TempPath};
+
+    const MEMINFO: &str = "MemTotal:        3019740 kB
+MemFree:         1809728 kB
+MemAvailable:    2546560 kB
+Buffers:           54736 kB
+Cached:           776052 kB
+SwapCached:            0 kB
+Active:           445856 kB
+Inactive:         459092 kB
+Active(anon):      78492 kB
+Inactive(anon):     2240 kB
+Active(file):     367364 kB
+Inactive(file):   456852 kB
+Unevictable:        3096 kB
+Mlocked:            3096 kB
+SwapTotal:         32768 kB
+SwapFree:           4096 kB
+Dirty:                32 kB
+Writeback:             0 kB
+AnonPages:         74988 kB
+Mapped:            62624 kB
+Shmem:              4020 kB
+KReclaimable:      87324 kB
+Slab:              86464 kB
+SReclaimable:      44432 kB
+SUnreclaim:        42032 kB
+KernelStack:        4880 kB
+PageTables:         2900 kB
+NFS_Unstable:          0 kB
+Bounce:                0 kB
+WritebackTmp:          0 kB
+CommitLimit:     1509868 kB
+Committed_AS:      80296 kB
+VmallocTotal:   263061440 kB
+VmallocUsed:       65536 kB
+VmallocChunk:          0 kB
+AnonHugePages:      6144 kB
+ShmemHugePages:        0 kB
+ShmemPmdMapped:        0 kB
+CmaTotal:         131072 kB
+CmaFree:          130380 kB
+HugePages_Total:       0
+HugePages_Free:        0
+HugePages_Rsvd:        0
+HugePages_Surp:        0
+Hugepagesize:       2048 kB";
+
+    const VMALLOC_IOREMAP: &str =
+        "0x0000000000000000-0x0000000000000000   69632 of_iomap+0x78/0xb0 phys=17a00000 ioremap
+0x0000000000000000-0x0000000000000000    8192 of_iomap+0x78/0xb0 phys=b220000 ioremap
+0x0000000000000000-0x0000000000000000    8192 of_iomap+0x78/0xb0 phys=17c90000 ioremap
+0x0000000000000000-0x0000000000000000    8192 of_iomap+0x78/0xb0 phys=17ca0000 ioremap\n";
+    const VMALLOC_KERNEL: &str = "0x0000000000000000-0x0000000000000000    8192 drm_property_create_blob+0x44/0xec pages=1 vmalloc\n";
+    const VMALLOC_MODULE: &str = "0x0000000000000000-0x0000000000000000   28672 pktlog_alloc_buf+0xc4/0x15c [wlan] pages=6 vmalloc\n";
+
+    #[test]
+    fn test_sys_mem_info_file() {
+        let path = write_temp_file("meminfo", MEMINFO);
+        let mut mi = SysMemInfo::new();
+        mi.read_mem_info_from(&path).unwrap();
+        assert_eq!(mi.mem_total_kb(), 3019740);
+        assert_eq!(mi.mem_free_kb(), 1809728);
+        assert_eq!(mi.mem_buffers_kb(), 54736);
+        assert_eq!(mi.mem_cached_kb(), 776052);
+        assert_eq!(mi.mem_shmem_kb(), 4020);
+        assert_eq!(mi.mem_slab_kb(), 86464);
+        assert_eq!(mi.mem_slab_reclaimable_kb(), 44432);
+        assert_eq!(mi.mem_slab_unreclaimable_kb(), 42032);
+        assert_eq!(mi.mem_swap_kb(), 32768);
+        assert_eq!(mi.mem_swap_free_kb(), 4096);
+        assert_eq!(mi.mem_mapped_kb(), 62624);
+        assert_eq!(mi.mem_vmalloc_used_kb(), 65536);
+        assert_eq!(mi.mem_page_tables_kb(), 2900);
+        assert_eq!(mi.mem_kernel_stack_kb(), 4880);
+        assert_eq!(mi.mem_kreclaimable_kb(), 87324);
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let path = write_temp_file("meminfo_empty", "");
+        let mut mi = SysMemInfo::new();
+        assert!(mi.read_mem_info_from(&path).is_ok());
+        assert_eq!(mi.mem_total_kb(), 0);
+    }
+
+    #[test]
+    fn test_malformed_file() {
+        let path = write_temp_file("meminfo_malformed", "MemTotal:        abc kB\n");
+        let mut mi = SysMemInfo::new();
+        assert!(mi.read_mem_info_from(&path).is_err());
+    }
+
+    #[test]
+    fn test_zram_total() {
+        let mi = SysMemInfo::new();
+        assert_eq!(mi.mem_zram_kb(Some(&testdata_dir("testdata1"))), 30504);
+        assert_eq!(mi.mem_zram_kb(Some(&testdata_dir("testdata2"))), 30504);
+    }
+
+    #[test]
+    fn test_zram_with_tags() {
+        let path = write_temp_file("meminfo_tags", MEMINFO);
+        let mut tags = SysMemInfo::DEFAULT_SYS_MEMINFO_TAGS.to_vec();
+        tags.insert(10, ZRAM_TAG);
+        tags.push("Unevictable:");
+        let mi = SysMemInfo::new();
+        let mem = mi.read_mem_info_tags(&tags, &path).unwrap();
+        assert_eq!(mem.len(), tags.len());
+        assert_eq!(mem[0], 3019740);
+        assert_eq!(mem[1], 1809728);
+        assert_eq!(mem[2], 54736);
+        assert_eq!(mem[3], 776052);
+        assert_eq!(mem[4], 4020);
+        assert_eq!(mem[5], 86464);
+        assert_eq!(mem[6], 44432);
+        assert_eq!(mem[7], 42032);
+        assert_eq!(mem[8], 32768);
+        assert_eq!(mem[9], 4096);
+        assert_eq!(mem[11], 62624);
+        assert_eq!(mem[12], 65536);
+        assert_eq!(mem[13], 2900);
+        assert_eq!(mem[14], 4880);
+        assert_eq!(mem[15], 87324);
+        assert_eq!(mem[16], 3096);
+    }
+
+    #[test]
+    fn test_vmalloc_info_no_memory() {
+        let path = write_temp_file("vmalloc_none", VMALLOC_IOREMAP);
+        assert_eq!(read_vmalloc_info(&path), 0);
+    }
+
+    #[test]
+    fn test_vmalloc_info_kernel() {
+        let path = write_temp_file("vmalloc_kernel", VMALLOC_KERNEL);
+        assert_eq!(read_vmalloc_info(&path), page_size());
+    }
+
+    #[test]
+    fn test_vmalloc_info_module() {
+        let path = write_temp_file("vmalloc_module", VMALLOC_MODULE);
+        assert_eq!(read_vmalloc_info(&path), 6 * page_size());
+    }
+
+    #[test]
+    fn test_vmalloc_info_all() {
+        let content = [VMALLOC_IOREMAP, VMALLOC_KERNEL, VMALLOC_MODULE].concat();
+        let path = write_temp_file("vmalloc_all", &content);
+        assert_eq!(read_vmalloc_info(&path), 7 * page_size());
+    }
+
+    #[test]
+    fn test_read_vmstat() {
+        let path = write_temp_file("vmstat", "nr_free_pages 7774\nnr_zone_inactive_anon 98\n");
+        let vmstat = read_vmstat(&path).unwrap();
+        assert_eq!(vmstat.len(), 2);
+        assert_eq!(vmstat["nr_free_pages"], 7774);
+        assert_eq!(vmstat["nr_zone_inactive_anon"], 98);
+    }
+
+    #[test]
+    fn test_read_ion_heaps_size_kb() {
+        let path = write_temp_file("total_heaps_kb", "98480");
+        assert_eq!(read_ion_heaps_size_kb(&path).unwrap(), 98480);
+    }
+
+    #[test]
+    fn test_read_ion_pools_size_kb() {
+        let path = write_temp_file("total_pools_kb", "416");
+        assert_eq!(read_ion_pools_size_kb(&path).unwrap(), 416);
+    }
+
+    #[test]
+    fn test_read_dmabuf_total_exported_kb() {
+        let dir = TempPath::new("dmabuf");
+        for (inode, size) in [("1001", "1048576"), ("1002", "4096")] {
+            fs::create_dir_all(dir.as_ref().join(inode)).unwrap();
+            fs::write(dir.as_ref().join(inode).join("size"), size).unwrap();
+        }
+        assert_eq!(read_dmabuf_total_exported_kb(&dir).unwrap(), 1028);
+    }
+}
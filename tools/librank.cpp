@@ -21,6 +21,7 @@
 #include <linux/kernel-page-flags.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
 #include <sys/mman.h>
 #include <sys/types.h>
 #include <unistd.h>
@@ -43,7 +44,7 @@ using ::android::meminfo::Vma;
 
 [[noreturn]] static void usage(int exit_status) {
     fprintf(stderr,
-            "Usage: %s [ -P | -L ] [ -v | -r | -p | -u | -s | -h ]\n"
+            "Usage: %s [ -P | -L ] [ -g ] [ -i pid ] [ -j ] [ -v | -r | -p | -u | -s | -h ]\n"
             "\n"
             "Sort options:\n"
             "    -v  Sort processes by VSS.\n"
@@ -59,6 +60,11 @@ using ::android::meminfo::Vma;
             "    -c  Only show cached (storage backed) pages\n"
             "    -C  Only show non-cached (ram/swap backed) pages\n"
             "    -k  Only show pages collapsed by KSM\n"
+            "    -g  Group [anon:...] mappings by category, e.g. all thread stacks together\n"
+            "        and all dalvik or scudo heaps together.\n"
+            "    -i pid  Only scan the process with this pid.\n"
+            "    -j  Print the output in JSON format, all sizes are in kB. The output is an\n"
+            "        object with a schema_version and the list of libs.\n"
             "    -h  Display this help screen.\n",
             getprogname());
     exit(exit_status);
@@ -151,6 +157,16 @@ static bool g_all_libs = false;
 static bool g_has_swap = false;
 static bool g_reverse_sort = false;
 static std::string g_prefix_filter = "";
+static bool g_json = false;
+static bool g_group_anon = false;
+static pid_t g_pid = -1;
+// Number of processes that exited while they were being scanned
+static int g_nr_exited = 0;
+
+static bool process_exited(pid_t pid) {
+    std::string procdir = ::android::base::StringPrintf("/proc/%d", pid);
+    return access(procdir.c_str(), F_OK) != 0;
+}
 
 static bool read_all_pids(std::function<bool(pid_t pid)> for_each_pid) {
     std::unique_ptr<DIR, int (*)(DIR*)> procdir(opendir("/proc"), closedir);
@@ -166,16 +182,46 @@ static bool read_all_pids(std::function<bool(pid_t pid)> for_each_pid) {
     return true;
 }
 
+// Returns the category that an "[anon:<name>]" mapping is shown under with -g, other names
+// are returned as is.
+static std::string anon_category(const std::string& name) {
+    if (!::android::base::StartsWith(name, "[anon:") || !::android::base::EndsWith(name, "]")) {
+        return name;
+    }
+    // The java heap, the zygote space, linear alloc etc. of ART
+    if (::android::base::StartsWith(name, "[anon:dalvik-")) {
+        return "[anon:dalvik-*]";
+    }
+    // The primary and secondary allocator regions of the scudo native heap
+    if (::android::base::StartsWith(name, "[anon:scudo:")) {
+        return "[anon:scudo:*]";
+    }
+    // Per-thread mappings end with the thread id, e.g. "[anon:stack_and_tls:1234]"
+    size_t pos = name.rfind(':');
+    pid_t tid;
+    if (pos > strlen("[anon") &&
+        ::android::base::ParseInt(name.substr(pos + 1, name.size() - pos - 2), &tid)) {
+        return name.substr(0, pos) + "]";
+    }
+    return name;
+}
+
 static bool scan_libs_per_process(pid_t pid) {
     ProcMemInfo pmem(pid, false, g_pgflags, g_pgflags_mask);
     const std::vector<Vma> maps = pmem.Maps();
     if (maps.size() == 0) {
         // nothing to do here, continue
+        if (process_exited(pid)) g_nr_exited++;
         return true;
     }
 
     ProcessRecord proc(pid);
     if (!proc.valid()) {
+        // The process was most likely killed after its maps were read, skip it.
+        if (process_exited(pid)) {
+            g_nr_exited++;
+            return true;
+        }
         fprintf(stderr, "Failed to create process record for process: %d\n", pid);
         return false;
     }
@@ -197,8 +243,9 @@ static bool scan_libs_per_process(pid_t pid) {
             continue;
         }
 
+        std::string name = g_group_anon ? anon_category(map.name) : map.name;
         auto [it, inserted] =
-            g_libs.insert(std::pair<std::string, LibRecord>(map.name, LibRecord(map.name)));
+            g_libs.insert(std::pair<std::string, LibRecord>(name, LibRecord(name)));
         it->second.AddUsage(proc, map.usage);
 
         if (!g_has_swap && map.usage.swap) {
@@ -209,6 +256,83 @@ static bool scan_libs_per_process(pid_t pid) {
     return true;
 }
 
+// Returns the length of the valid UTF-8 sequence that starts at 'in[pos]', or 0 if there is none.
+static size_t utf8_sequence_length(const std::string& in, size_t pos) {
+    unsigned char c = in[pos];
+    size_t len;
+    // The range of the 2nd byte is narrower for some lead bytes to rule out overlong encodings,
+    // surrogates and code points above U+10FFFF.
+    unsigned char min = 0x80, max = 0xbf;
+    if (c >= 0xc2 && c <= 0xdf) {
+        len = 2;
+    } else if (c >= 0xe0 && c <= 0xef) {
+        len = 3;
+        if (c == 0xe0) min = 0xa0;
+        if (c == 0xed) max = 0x9f;
+    } else if (c >= 0xf0 && c <= 0xf4) {
+        len = 4;
+        if (c == 0xf0) min = 0x90;
+        if (c == 0xf4) max = 0x8f;
+    } else {
+        return 0;
+    }
+
+    if (pos + len > in.size()) return 0;
+    for (size_t i = 1; i < len; i++) {
+        unsigned char next = in[pos + i];
+        if (next < min || next > max) return 0;
+        min = 0x80;
+        max = 0xbf;
+    }
+    return len;
+}
+
+// Mapping names are file names that don't have to be valid UTF-8, bytes that aren't part of a
+// valid UTF-8 sequence are replaced with U+FFFD so that the output is always valid JSON.
+static std::string escape_json(const std::string& in) {
+    std::string out;
+    for (size_t i = 0; i < in.size(); i++) {
+        unsigned char c = in[i];
+        if (c == '"' || c == '\\') {
+            out += '\\';
+            out += c;
+        } else if (c < 0x20) {
+            out += ::android::base::StringPrintf("\\u%04x", c);
+        } else if (c < 0x80) {
+            out += c;
+        } else {
+            size_t len = utf8_sequence_length(in, i);
+            if (len == 0) {
+                out += "\\ufffd";
+            } else {
+                out.append(in, i, len);
+                i += len - 1;
+            }
+        }
+    }
+    return out;
+}
+
+// Version of the -j output. It only changes when a field is removed, renamed or changes its
+// meaning, new fields may be added without changing it.
+static constexpr int kJsonSchemaVersion = 1;
+
+static void print_lib_json(const LibRecord& lib, const std::vector<ProcessRecord>& procs,
+                           bool first) {
+    printf("%s\n    {\"name\": \"%s\", \"pss_tot\": %" PRIu64 ", \"processes\": [",
+           first ? "" : ",", escape_json(lib.name()).c_str(), lib.pss() / 1024);
+    for (size_t i = 0; i < procs.size(); i++) {
+        const MemUsage& usage = procs[i].usage();
+        printf("%s\n      {\"pid\": %d, \"cmdline\": \"%s\", \"vss\": %" PRIu64
+               ", \"rss\": %" PRIu64 ", \"pss\": %" PRIu64 ", \"uss\": %" PRIu64
+               ", \"swap\": %" PRIu64 "}",
+               i ? "," : "", procs[i].pid(), escape_json(procs[i].cmdline()).c_str(),
+               usage.vss / 1024, usage.rss / 1024, usage.pss / 1024, usage.uss / 1024,
+               usage.swap / 1024);
+    }
+    printf("\n    ]}");
+}
+
 static uint16_t parse_mapflags(const char* mapflags) {
     uint16_t ret = 0;
     for (const char* p = mapflags; *p; p++) {
@@ -255,7 +379,7 @@ int main(int argc, char* argv[]) {
 
     std::function<bool(const ProcessRecord&, const ProcessRecord&)> sort_func = pss_sort;
 
-    while ((opt = getopt(argc, argv, "acChkm:pP:uvrsR")) != -1) {
+    while ((opt = getopt(argc, argv, "acCghi:jkm:pP:uvrsR")) != -1) {
         switch (opt) {
             case 'a':
                 g_all_libs = true;
@@ -267,8 +391,19 @@ int main(int argc, char* argv[]) {
             case 'C':
                 g_pgflags = g_pgflags_mask = (1 << KPF_SWAPBACKED);
                 break;
+            case 'g':
+                g_group_anon = true;
+                break;
             case 'h':
                 usage(EXIT_SUCCESS);
+            case 'i':
+                if (!::android::base::ParseInt(optarg, &g_pid, 1)) {
+                    error(EXIT_FAILURE, 0, "Invalid pid: %s", optarg);
+                }
+                break;
+            case 'j':
+                g_json = true;
+                break;
             case 'k':
                 g_pgflags = g_pgflags_mask = (1 << KPF_KSM);
                 break;
@@ -301,15 +436,31 @@ int main(int argc, char* argv[]) {
         }
     }
 
-    if (!read_all_pids(scan_libs_per_process)) {
+    // Without access to the page flags and counts, the usage of every map would be 0.
+    if (access("/proc/kpageflags", R_OK)) {
+        error(EXIT_FAILURE, errno, "requires root, can't read /proc/kpageflags");
+    }
+
+    if (g_pid != -1) {
+        if (process_exited(g_pid)) {
+            error(EXIT_FAILURE, 0, "No such process: %d", g_pid);
+        }
+        if (!scan_libs_per_process(g_pid)) {
+            error(EXIT_FAILURE, 0, "Failed to scan process: %d", g_pid);
+        }
+    } else if (!read_all_pids(scan_libs_per_process)) {
         error(EXIT_FAILURE, 0, "Failed to read all pids from the system");
     }
 
-    printf(" %6s   %7s   %6s   %6s   %6s  ", "RSStot", "VSS", "RSS", "PSS", "USS");
-    if (g_has_swap) {
-        printf(" %6s  ", "Swap");
+    if (g_json) {
+        printf("{\n  \"schema_version\": %d,\n  \"libs\": [", kJsonSchemaVersion);
+    } else {
+        printf(" %6s   %7s   %6s   %6s   %6s  ", "RSStot", "VSS", "RSS", "PSS", "USS");
+        if (g_has_swap) {
+            printf(" %6s  ", "Swap");
+        }
+        printf("Name/PID\n");
     }
-    printf("Name/PID\n");
 
     std::vector<LibRecord> v_libs;
     v_libs.reserve(g_libs.size());
@@ -320,13 +471,8 @@ int main(int argc, char* argv[]) {
     std::sort(v_libs.begin(), v_libs.end(),
               [](const LibRecord& l1, const LibRecord& l2) { return l1.pss() > l2.pss(); });
 
+    bool first = true;
     for (auto& lib : v_libs) {
-        printf("%6" PRIu64 "K   %7s   %6s   %6s   %6s  ", lib.pss() / 1024, "", "", "", "");
-        if (g_has_swap) {
-            printf(" %6s  ", "");
-        }
-        printf("%s\n", lib.name().c_str());
-
         // sort all mappings first
 
         std::vector<ProcessRecord> procs;
@@ -336,6 +482,18 @@ int main(int argc, char* argv[]) {
 
         std::sort(procs.begin(), procs.end(), sort_func);
 
+        if (g_json) {
+            print_lib_json(lib, procs, first);
+            first = false;
+            continue;
+        }
+
+        printf("%6" PRIu64 "K   %7s   %6s   %6s   %6s  ", lib.pss() / 1024, "", "", "", "");
+        if (g_has_swap) {
+            printf(" %6s  ", "");
+        }
+        printf("%s\n", lib.name().c_str());
+
         for (auto& p : procs) {
             const MemUsage& usage = p.usage();
             printf(" %6s  %7" PRIu64 "K  %6" PRIu64 "K  %6" PRIu64 "K  %6" PRIu64 "K  ", "",
@@ -347,5 +505,15 @@ int main(int argc, char* argv[]) {
         }
     }
 
+    if (g_json) {
+        printf("\n  ]\n}\n");
+    }
+
+    if (g_nr_exited) {
+        // Keep the JSON output parsable
+        fprintf(g_json ? stderr : stdout,
+                "%d process(es) exited during the scan and are not included\n", g_nr_exited);
+    }
+
     return 0;
 }
@@ -20,6 +20,7 @@
 #include <android-base/strings.h>
 #include <dirent.h>
 #include <errno.h>
+#include <getopt.h>
 #include <inttypes.h>
 #include <linux/kernel-page-flags.h>
 #include <linux/oom.h>
@@ -27,26 +28,57 @@
 #include <meminfo/sysmeminfo.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
+#include <sys/stat.h>
 #include <sys/types.h>
 #include <unistd.h>
 
+#include <algorithm>
 #include <iostream>
+#include <map>
 #include <memory>
 #include <sstream>
+#include <unordered_map>
 #include <vector>
 
 using ::android::meminfo::MemUsage;
 using ::android::meminfo::ProcMemInfo;
+using ::android::meminfo::Vma;
+
+// The memory statistics that are shown for a process, a process family or a group of processes.
+struct MemStats {
+    MemUsage usage;
+    uint64_t proportional_swap = 0;
+    uint64_t unique_swap = 0;
+    uint64_t zswap = 0;
+    // Size of the locked mappings and the proportional size of their resident pages
+    uint64_t vmlck = 0;
+    uint64_t locked = 0;
+    int32_t oomadj = OOM_SCORE_ADJ_MAX + 1;
+
+    // Adds the memory of 'other', the oomadj is kept as is.
+    void Add(const MemStats& other) {
+        usage.vss += other.usage.vss;
+        usage.rss += other.usage.rss;
+        usage.pss += other.usage.pss;
+        usage.uss += other.usage.uss;
+        usage.swap += other.usage.swap;
+        proportional_swap += other.proportional_swap;
+        unique_swap += other.unique_swap;
+        zswap += other.zswap;
+        vmlck += other.vmlck;
+        locked += other.locked;
+    }
+};
 
 struct ProcessRecord {
   public:
     ProcessRecord(pid_t pid, bool get_wss = false, uint64_t pgflags = 0, uint64_t pgflags_mask = 0)
         : pid_(-1),
-          oomadj_(OOM_SCORE_ADJ_MAX + 1),
+          ppid_(0),
+          uid_(0),
           cmdline_(""),
-          proportional_swap_(0),
-          unique_swap_(0),
-          zswap_(0) {
+          has_maps_(false) {
         std::unique_ptr<ProcMemInfo> procmem =
                 std::make_unique<ProcMemInfo>(pid, get_wss, pgflags, pgflags_mask);
         if (procmem == nullptr) {
@@ -62,7 +94,7 @@ struct ProcessRecord {
             return;
         }
 
-        if (fscanf(oomscore_fp.get(), "%d\n", &oomadj_) != 1) {
+        if (fscanf(oomscore_fp.get(), "%d\n", &stats_.oomadj) != 1) {
             std::cerr << "Failed to read oomadj from: " << fname << std::endl;
             return;
         }
@@ -78,43 +110,99 @@ struct ProcessRecord {
         // The .c_str() assignment below then takes care of trimming the cmdline at the first
         // 0x00. This is how original procrank worked (luckily)
         cmdline_.resize(strlen(cmdline_.c_str()));
-        usage_or_wss_ = get_wss ? procmem->Wss() : procmem->Usage();
-        swap_offsets_ = procmem->SwapOffsets();
+        // The maps are read first, if the process exits while they are read they are left
+        // empty but the usage may have already been accounted for some of them.
+        has_maps_ = !procmem->Maps().empty();
+        if (has_maps_) {
+            stats_.usage = get_wss ? procmem->Wss() : procmem->Usage();
+            swap_offsets_ = procmem->SwapOffsets();
+        }
         pid_ = pid;
     }
 
     bool valid() const { return pid_ != -1; }
+    bool has_maps() const { return has_maps_; }
+
+    // The parent pid is only needed to build the process tree, processes whose
+    // parent can't be read are shown at the top level.
+    void ReadParentPid() {
+        std::string procstat;
+        std::string fname = ::android::base::StringPrintf("/proc/%d/stat", pid_);
+        if (!::android::base::ReadFileToString(fname, &procstat)) return;
+        // The command name in the 2nd field may itself contain spaces and parentheses,
+        // so look for the state and ppid after the last ')'.
+        size_t pos = procstat.rfind(')');
+        if (pos != std::string::npos) {
+            sscanf(procstat.c_str() + pos + 1, " %*c %d", &ppid_);
+        }
+    }
+
+    void ReadUid() {
+        struct stat st;
+        std::string fname = ::android::base::StringPrintf("/proc/%d", pid_);
+        if (stat(fname.c_str(), &st) == 0) {
+            uid_ = st.st_uid;
+        }
+    }
+
+    // Memory locked with mlock() can't be reclaimed, see -l. Reads VmLck from status and
+    // sums up the Locked: fields of smaps_rollup, or of smaps if the kernel has no rollup.
+    void ReadLocked() {
+        std::string content;
+        std::string fname = ::android::base::StringPrintf("/proc/%d/status", pid_);
+        if (::android::base::ReadFileToString(fname, &content)) {
+            size_t pos = content.find("\nVmLck:");
+            if (pos != std::string::npos &&
+                sscanf(content.c_str() + pos, "\nVmLck: %" SCNu64, &stats_.vmlck) == 1) {
+                stats_.vmlck *= 1024;
+            }
+        }
+
+        fname = ::android::base::StringPrintf(
+                "/proc/%d/%s", pid_,
+                ::android::meminfo::IsSmapsRollupSupported(pid_) ? "smaps_rollup" : "smaps");
+        if (!::android::base::ReadFileToString(fname, &content)) return;
+        for (const auto& line : ::android::base::Split(content, "\n")) {
+            uint64_t locked;
+            if (sscanf(line.c_str(), "Locked: %" SCNu64, &locked) == 1) {
+                stats_.locked += locked * 1024;
+            }
+        }
+    }
 
     void CalculateSwap(const std::vector<uint16_t>& swap_offset_array,
                        float zram_compression_ratio) {
         for (auto& off : swap_offsets_) {
-            proportional_swap_ += getpagesize() / swap_offset_array[off];
-            unique_swap_ += swap_offset_array[off] == 1 ? getpagesize() : 0;
-            zswap_ = proportional_swap_ * zram_compression_ratio;
+            stats_.proportional_swap += getpagesize() / swap_offset_array[off];
+            stats_.unique_swap += swap_offset_array[off] == 1 ? getpagesize() : 0;
+            stats_.zswap = stats_.proportional_swap * zram_compression_ratio;
         }
     }
 
     // Getters
     pid_t pid() const { return pid_; }
+    pid_t ppid() const { return ppid_; }
+    uid_t uid() const { return uid_; }
+    uint64_t locked() const { return stats_.locked; }
     const std::string& cmdline() const { return cmdline_; }
-    int32_t oomadj() const { return oomadj_; }
-    uint64_t proportional_swap() const { return proportional_swap_; }
-    uint64_t unique_swap() const { return unique_swap_; }
-    uint64_t zswap() const { return zswap_; }
+    int32_t oomadj() const { return stats_.oomadj; }
+    uint64_t proportional_swap() const { return stats_.proportional_swap; }
+    uint64_t unique_swap() const { return stats_.unique_swap; }
+    uint64_t zswap() const { return stats_.zswap; }
+    const MemStats& Stats() const { return stats_; }
 
     // Wrappers to ProcMemInfo
     const std::vector<uint64_t>& SwapOffsets() const { return swap_offsets_; }
-    const MemUsage& Usage() const { return usage_or_wss_; }
-    const MemUsage& Wss() const { return usage_or_wss_; }
+    const MemUsage& Usage() const { return stats_.usage; }
+    const MemUsage& Wss() const { return stats_.usage; }
 
   private:
     pid_t pid_;
-    int32_t oomadj_;
+    pid_t ppid_;
+    uid_t uid_;
     std::string cmdline_;
-    uint64_t proportional_swap_;
-    uint64_t unique_swap_;
-    uint64_t zswap_;
-    MemUsage usage_or_wss_;
+    bool has_maps_;
+    MemStats stats_;
     std::vector<uint64_t> swap_offsets_;
 };
 
@@ -132,6 +220,17 @@ bool has_zram = false;
 float zram_compression_ratio = 0.0;
 // Sort process in reverse, default is descending
 bool reverse_sort = false;
+// Show processes as a tree with the memory of all children rolled up into their parent
+bool show_tree = false;
+
+// Show how much of the summed up RSS is memory mapped into more than one process
+bool show_dedup = false;
+// Show locked memory per process and for the system
+bool show_locked = false;
+
+enum class GroupBy { NONE, UID, PACKAGE, CGROUP };
+// Aggregate the memory of processes by user id, package or cgroup instead of showing each process
+GroupBy group_by = GroupBy::NONE;
 
 // Calculated total memory usage across all processes in the system
 uint64_t total_pss = 0;
@@ -140,10 +239,16 @@ uint64_t total_swap = 0;
 uint64_t total_pswap = 0;
 uint64_t total_uswap = 0;
 uint64_t total_zswap = 0;
+uint64_t total_locked = 0;
+// Number of processes that exited while they were being scanned
+int nr_exited = 0;
+// Number of processes that are still around but whose memory usage couldn't be read
+int nr_unreadable = 0;
 
 [[noreturn]] static void usage(int exit_status) {
-    std::cerr << "Usage: " << getprogname() << " [ -W ] [ -v | -r | -p | -u | -s | -h ]"
-              << std::endl
+    std::cerr << "Usage: " << getprogname()
+              << " [ -W ] [ -d ] [ -l ] [ -t | --group-by uid|package|cgroup ]"
+              << " [ -v | -r | -p | -u | -s | -h ]" << std::endl
               << "    -v  Sort by VSS." << std::endl
               << "    -r  Sort by RSS." << std::endl
               << "    -p  Sort by PSS." << std::endl
@@ -158,10 +263,44 @@ uint64_t total_zswap = 0;
               << "    -W  Reset working set of all processes." << std::endl
               << "    -o  Show and sort by oom score against lowmemorykiller thresholds."
               << std::endl
+              << "    -l  Show the size of the locked (mlock) mappings and sort by the PSS of their"
+              << std::endl
+              << "        resident pages, also shows unevictable memory." << std::endl
+              << "    -d, --dedup" << std::endl
+              << "        Also show the RSS summed over all processes next to the memory used by"
+              << std::endl
+              << "        distinct physical pages, for file, anon and ashmem/memfd mappings."
+              << std::endl
+              << "        Counts all resident pages, -c, -C, -k and -w don't apply to it."
+              << std::endl
+              << "    -t, --tree" << std::endl
+              << "        Show processes as a tree, each process includes the memory of all its"
+              << std::endl
+              << "        descendants. Totals still count every process once." << std::endl
+              << "    --group-by uid|package|cgroup" << std::endl
+              << "        Show the memory of all processes running as the same uid, the same"
+              << std::endl
+              << "        package from /data/system/packages.list or in the same cgroup as a"
+              << std::endl
+              << "        single row. Per-process pid_<pid> cgroups are merged into their parent."
+              << std::endl
+              << "        Uids below 10000 are shown as numbers, not as a package." << std::endl
               << "    -h  Display this help screen." << std::endl;
     exit(exit_status);
 }
 
+static bool process_exited(pid_t pid) {
+    std::string procdir = ::android::base::StringPrintf("/proc/%d", pid);
+    return access(procdir.c_str(), F_OK) != 0;
+}
+
+// Kernel threads have no address space, so their maps file reads fine but is empty.
+static bool is_kernel_thread(pid_t pid) {
+    std::string maps;
+    std::string fname = ::android::base::StringPrintf("/proc/%d/maps", pid);
+    return ::android::base::ReadFileToString(fname, &maps) && maps.empty();
+}
+
 static bool read_all_pids(std::vector<pid_t>* pids, std::function<bool(pid_t pid)> for_each_pid) {
     pids->clear();
     std::unique_ptr<DIR, int (*)(DIR*)> procdir(opendir("/proc"), closedir);
@@ -200,9 +339,22 @@ static bool count_swap_offsets(const ProcessRecord& proc,
     return true;
 }
 
+static const char* group_by_name() {
+    switch (group_by) {
+        case GroupBy::UID:
+            return "uid";
+        case GroupBy::PACKAGE:
+            return "package";
+        case GroupBy::CGROUP:
+            return "cgroup";
+        default:
+            return "cmdline";
+    }
+}
+
 static void print_header(std::stringstream& ss) {
     ss.str("");
-    ss << ::android::base::StringPrintf("%5s  ", "PID");
+    ss << ::android::base::StringPrintf("%5s  ", group_by == GroupBy::NONE ? "PID" : "Procs");
     if (show_oomadj) {
         ss << ::android::base::StringPrintf("%5s  ", "oom");
     }
@@ -220,40 +372,254 @@ static void print_header(std::stringstream& ss) {
         }
     }
 
-    ss << "cmdline";
-}
-
-static void print_process_record(std::stringstream& ss, ProcessRecord& proc) {
-    ss << ::android::base::StringPrintf("%5d  ", proc.pid());
-    if (show_oomadj) {
-        ss << ::android::base::StringPrintf("%5d  ", proc.oomadj());
+    if (show_locked) {
+        ss << ::android::base::StringPrintf("%7s  %7s  ", "VmLck", "Locked");
     }
 
+    ss << group_by_name();
+}
+
+static void print_process_usage(std::stringstream& ss, const MemStats& stats) {
     if (show_wss) {
         ss << ::android::base::StringPrintf("%6" PRIu64 "K  %6" PRIu64 "K  %6" PRIu64 "K  ",
-                                            proc.Wss().rss / 1024, proc.Wss().pss / 1024,
-                                            proc.Wss().uss / 1024);
+                                            stats.usage.rss / 1024, stats.usage.pss / 1024,
+                                            stats.usage.uss / 1024);
     } else {
         ss << ::android::base::StringPrintf("%7" PRIu64 "K  %6" PRIu64 "K  %6" PRIu64 "K  %6" PRIu64
                                             "K  ",
-                                            proc.Usage().vss / 1024, proc.Usage().rss / 1024,
-                                            proc.Usage().pss / 1024, proc.Usage().uss / 1024);
+                                            stats.usage.vss / 1024, stats.usage.rss / 1024,
+                                            stats.usage.pss / 1024, stats.usage.uss / 1024);
         if (has_swap) {
-            ss << ::android::base::StringPrintf("%6" PRIu64 "K  ", proc.Usage().swap / 1024);
-            ss << ::android::base::StringPrintf("%6" PRIu64 "K  ", proc.proportional_swap() / 1024);
-            ss << ::android::base::StringPrintf("%6" PRIu64 "K  ", proc.unique_swap() / 1024);
+            ss << ::android::base::StringPrintf("%6" PRIu64 "K  ", stats.usage.swap / 1024);
+            ss << ::android::base::StringPrintf("%6" PRIu64 "K  ", stats.proportional_swap / 1024);
+            ss << ::android::base::StringPrintf("%6" PRIu64 "K  ", stats.unique_swap / 1024);
             if (has_zram) {
-                ss << ::android::base::StringPrintf("%6" PRIu64 "K  ", (proc.zswap() / 1024));
+                ss << ::android::base::StringPrintf("%6" PRIu64 "K  ", (stats.zswap / 1024));
             }
         }
     }
+
+    if (show_locked) {
+        ss << ::android::base::StringPrintf("%6" PRIu64 "K  %6" PRIu64 "K  ", stats.vmlck / 1024,
+                                            stats.locked / 1024);
+    }
+}
+
+static void print_process_record(std::stringstream& ss, const ProcessRecord& proc,
+                                 const MemStats& stats) {
+    ss << ::android::base::StringPrintf("%5d  ", proc.pid());
+    if (show_oomadj) {
+        ss << ::android::base::StringPrintf("%5d  ", proc.oomadj());
+    }
+    print_process_usage(ss, stats);
+}
+
+static void print_process_family(std::stringstream& ss, const std::vector<ProcessRecord>& procs,
+                                 const std::vector<MemStats>& families,
+                                 const std::vector<std::vector<size_t>>& children, size_t idx,
+                                 int depth) {
+    print_process_record(ss, procs[idx], families[idx]);
+    for (int i = 0; i < depth; i++) {
+        ss << "  ";
+    }
+    ss << (depth > 0 ? "\\_ " : "") << procs[idx].cmdline() << std::endl;
+
+    for (size_t child : children[idx]) {
+        print_process_family(ss, procs, families, children, child, depth + 1);
+    }
+}
+
+// Print processes under their parent with the memory of all their descendants rolled up into
+// them. Processes whose parent is not in the list (e.g. init, kthreadd or a parent that exited)
+// are printed at the top level. Siblings are sorted by the memory of their whole family.
+static void print_process_tree(
+        std::stringstream& ss, const std::vector<ProcessRecord>& procs,
+        const std::function<bool(const MemStats& a, const MemStats& b)>& proc_sort) {
+    std::unordered_map<pid_t, size_t> pid_to_index;
+    for (size_t i = 0; i < procs.size(); i++) {
+        pid_to_index[procs[i].pid()] = i;
+    }
+
+    std::vector<size_t> parents(procs.size());
+    std::vector<std::vector<size_t>> children(procs.size());
+    std::vector<size_t> roots;
+    for (size_t i = 0; i < procs.size(); i++) {
+        auto parent = pid_to_index.find(procs[i].ppid());
+        if (parent == pid_to_index.end() || parent->second == i) {
+            parents[i] = i;
+            roots.emplace_back(i);
+        } else {
+            parents[i] = parent->second;
+            children[parent->second].emplace_back(i);
+        }
+    }
+
+    // Every process comes after its parent in 'preorder', so walking it backwards adds each
+    // family to its parent only after all of its own descendants were added to it.
+    std::vector<size_t> preorder;
+    std::vector<size_t> pending(roots.rbegin(), roots.rend());
+    while (!pending.empty()) {
+        size_t idx = pending.back();
+        pending.pop_back();
+        preorder.emplace_back(idx);
+        pending.insert(pending.end(), children[idx].begin(), children[idx].end());
+    }
+
+    std::vector<MemStats> families(procs.size());
+    for (size_t i = 0; i < procs.size(); i++) {
+        families[i] = procs[i].Stats();
+    }
+    for (auto it = preorder.rbegin(); it != preorder.rend(); ++it) {
+        if (parents[*it] != *it) {
+            families[parents[*it]].Add(families[*it]);
+        }
+    }
+
+    auto family_sort = [&](size_t a, size_t b) { return proc_sort(families[a], families[b]); };
+    std::stable_sort(roots.begin(), roots.end(), family_sort);
+    for (auto& siblings : children) {
+        std::stable_sort(siblings.begin(), siblings.end(), family_sort);
+    }
+
+    for (size_t root : roots) {
+        print_process_family(ss, procs, families, children, root, 0);
+    }
+}
+
+// Returns the package names for each app id from /data/system/packages.list. Packages sharing
+// a uid are shown by the first one listed and the number of the others.
+static std::unordered_map<uid_t, std::string> read_package_names() {
+    std::unordered_map<uid_t, std::string> names;
+    std::unordered_map<uid_t, int> shared;
+    std::string content;
+    if (!::android::base::ReadFileToString("/data/system/packages.list", &content)) {
+        std::cerr << "warning: failed to read /data/system/packages.list, showing uids instead"
+                  << std::endl;
+        return names;
+    }
+
+    for (const auto& line : ::android::base::Split(content, "\n")) {
+        std::vector<std::string> fields = ::android::base::Split(line, " ");
+        uid_t uid;
+        if (fields.size() < 2 || !::android::base::ParseUint(fields[1], &uid)) continue;
+        if (names.emplace(uid, fields[0]).second) continue;
+        shared[uid]++;
+    }
+
+    for (const auto& [uid, count] : shared) {
+        names[uid] += ::android::base::StringPrintf(" (+%d)", count);
+    }
+    return names;
+}
+
+// Android puts each process in its own cgroup below the one of its uid, e.g.
+// "/uid_10123/pid_4567" or "/apps/uid_10123/pid_4567" for the v1 memory controller. Drops the
+// per-process component so that all processes of an app end up in the same group.
+static std::string strip_pid_cgroup(const std::string& path) {
+    size_t pos = path.rfind('/');
+    pid_t pid;
+    if (pos == std::string::npos || !::android::base::StartsWith(path.substr(pos + 1), "pid_") ||
+        !::android::base::ParseInt(path.substr(pos + 5), &pid)) {
+        return path;
+    }
+    return pos == 0 ? "/" : path.substr(0, pos);
+}
+
+// Returns the cgroup v2 path of the process, or the path in the v1 memory controller hierarchy
+// if the memory controller isn't mounted on the v2 hierarchy. Per-process cgroups are reported
+// as their parent cgroup.
+static std::string read_cgroup(pid_t pid) {
+    std::string content;
+    std::string fname = ::android::base::StringPrintf("/proc/%d/cgroup", pid);
+    if (!::android::base::ReadFileToString(fname, &content)) {
+        return "<unknown>";
+    }
+
+    // Each line is "hierarchy-ID:controller-list:cgroup-path", the v2 hierarchy has ID 0 and
+    // no controllers.
+    std::string v2_path;
+    for (const auto& line : ::android::base::Split(content, "\n")) {
+        std::vector<std::string> fields = ::android::base::Split(line, ":");
+        if (fields.size() < 3) continue;
+        std::vector<std::string> controllers = ::android::base::Split(fields[1], ",");
+        if (std::find(controllers.begin(), controllers.end(), "memory") != controllers.end()) {
+            return "memory:" + strip_pid_cgroup(fields[2]);
+        }
+        if (fields[0] == "0" && fields[1].empty()) {
+            v2_path = fields[2];
+        }
+    }
+
+    return v2_path.empty() ? "<unknown>" : strip_pid_cgroup(v2_path);
+}
+
+static std::string process_group(const ProcessRecord& proc,
+                                 const std::unordered_map<uid_t, std::string>& package_names) {
+    if (group_by == GroupBy::PACKAGE) {
+        // Apps of secondary users run with the same app id offset by AID_USER_OFFSET. The
+        // shared system uids below AID_APP_START are listed too, e.g. 1000 by every package
+        // that runs as system, but they are also used by native daemons, so show their number.
+        constexpr uid_t kUserOffset = 100000;
+        constexpr uid_t kAppStart = 10000;
+        uid_t appid = proc.uid() % kUserOffset;
+        auto name = package_names.find(appid);
+        if (appid >= kAppStart && name != package_names.end()) {
+            return name->second;
+        }
+    } else if (group_by == GroupBy::CGROUP) {
+        return read_cgroup(proc.pid());
+    }
+    return std::to_string(proc.uid());
+}
+
+static void print_process_groups(
+        std::stringstream& ss, const std::vector<ProcessRecord>& procs,
+        const std::function<bool(const MemStats& a, const MemStats& b)>& proc_sort) {
+    std::unordered_map<uid_t, std::string> package_names;
+    if (group_by == GroupBy::PACKAGE) {
+        package_names = read_package_names();
+    }
+
+    // Each group keeps the oomadj of its first process.
+    std::map<std::string, size_t> group_index;
+    std::vector<std::string> names;
+    std::vector<MemStats> groups;
+    std::vector<size_t> nr_procs;
+    for (const auto& proc : procs) {
+        std::string name = process_group(proc, package_names);
+        auto [it, inserted] = group_index.emplace(name, groups.size());
+        if (inserted) {
+            names.emplace_back(name);
+            groups.emplace_back(proc.Stats());
+            nr_procs.emplace_back(1);
+        } else {
+            groups[it->second].Add(proc.Stats());
+            nr_procs[it->second]++;
+        }
+    }
+
+    std::vector<size_t> order;
+    for (const auto& [name, idx] : group_index) {
+        order.emplace_back(idx);
+    }
+    std::stable_sort(order.begin(), order.end(),
+                     [&](size_t a, size_t b) { return proc_sort(groups[a], groups[b]); });
+
+    for (size_t idx : order) {
+        ss << ::android::base::StringPrintf("%5zu  ", nr_procs[idx]);
+        print_process_usage(ss, groups[idx]);
+        ss << names[idx] << std::endl;
+    }
 }
 
-static void print_processes(std::stringstream& ss, std::vector<ProcessRecord>& procs,
-                            const std::vector<uint16_t>& swap_offset_array) {
+static void print_processes(
+        std::stringstream& ss, std::vector<ProcessRecord>& procs,
+        const std::vector<uint16_t>& swap_offset_array,
+        const std::function<bool(const MemStats& a, const MemStats& b)>& proc_sort) {
     for (auto& proc : procs) {
         total_pss += show_wss ? proc.Wss().pss : proc.Usage().pss;
         total_uss += show_wss ? proc.Wss().uss : proc.Usage().uss;
+        total_locked += proc.locked();
         if (!show_wss && has_swap) {
             proc.CalculateSwap(swap_offset_array, zram_compression_ratio);
             total_swap += proc.Usage().swap;
@@ -264,8 +630,16 @@ static void print_processes(std::stringstream& ss, std::vector<ProcessRecord>& p
             }
         }
 
-        print_process_record(ss, proc);
-        ss << proc.cmdline() << std::endl;
+        if (!show_tree && group_by == GroupBy::NONE) {
+            print_process_record(ss, proc, proc.Stats());
+            ss << proc.cmdline() << std::endl;
+        }
+    }
+
+    if (show_tree) {
+        print_process_tree(ss, procs, proc_sort);
+    } else if (group_by != GroupBy::NONE) {
+        print_process_groups(ss, procs, proc_sort);
     }
 }
 
@@ -287,6 +661,10 @@ static void print_separator(std::stringstream& ss) {
         }
     }
 
+    if (show_locked) {
+        ss << ::android::base::StringPrintf("%7s  %7s  ", "", "------");
+    }
+
     ss << ::android::base::StringPrintf("%s", "------");
 }
 
@@ -311,9 +689,94 @@ static void print_totals(std::stringstream& ss) {
             }
         }
     }
+
+    if (show_locked) {
+        ss << ::android::base::StringPrintf("%7s  %6" PRIu64 "K  ", "", total_locked / 1024);
+    }
     ss << "TOTAL";
 }
 
+// Bits of a /proc/<pid>/pagemap entry, see Documentation/admin-guide/mm/pagemap.rst
+static constexpr uint64_t kPagemapPresent = 1ULL << 63;
+static constexpr uint64_t kPagemapPfnMask = (1ULL << 55) - 1;
+
+enum VmaType { VMA_FILE, VMA_ANON, VMA_ASHMEM, VMA_TYPE_COUNT };
+static const char* kVmaTypeNames[VMA_TYPE_COUNT] = {"file", "anon", "ashmem/memfd"};
+
+static VmaType vma_type(const Vma& vma) {
+    if (::android::base::StartsWith(vma.name, "/dev/ashmem") ||
+        ::android::base::StartsWith(vma.name, "/memfd:")) {
+        return VMA_ASHMEM;
+    }
+    return ::android::base::StartsWith(vma.name, "/") ? VMA_FILE : VMA_ANON;
+}
+
+struct DedupStats {
+    // Resident memory summed over all mappings, i.e. shared pages are counted every time
+    uint64_t rss = 0;
+    // Memory used by the distinct physical pages
+    uint64_t unique = 0;
+    // Memory used by the distinct physical pages mapped more than once
+    uint64_t shared = 0;
+};
+
+// Walks the pagemap of every process to find the physical pages that are mapped more than once.
+// Each physical page is accounted to the type of the first mapping it was found in.
+static void print_dedup_report(std::stringstream& ss, const std::vector<ProcessRecord>& procs,
+                               uint64_t mem_total_kb) {
+    std::vector<DedupStats> stats(VMA_TYPE_COUNT);
+    uint64_t pagesz = getpagesize();
+
+    // One byte per physical page: 0 if it wasn't seen yet, otherwise the type of the first
+    // mapping it was found in plus one, with kPfnShared set once it was found again. RAM doesn't
+    // necessarily start at PFN 0, so the array grows for the PFNs beyond MemTotal.
+    constexpr uint8_t kPfnShared = 0x80;
+    std::vector<uint8_t> pfns(mem_total_kb * 1024 / pagesz);
+
+    for (const auto& proc : procs) {
+        ProcMemInfo procmem(proc.pid());
+        for (const Vma& vma : procmem.MapsWithoutUsageStats()) {
+            std::vector<uint64_t> pagemap;
+            // The process or the mapping may be gone already, skip it.
+            if (!procmem.PageMap(vma, &pagemap)) continue;
+
+            VmaType type = vma_type(vma);
+            for (uint64_t entry : pagemap) {
+                if (!(entry & kPagemapPresent)) continue;
+
+                stats[type].rss += pagesz;
+                uint64_t pfn = entry & kPagemapPfnMask;
+                if (pfn >= pfns.size()) {
+                    pfns.resize(std::max<uint64_t>(pfn + 1, pfns.size() * 2));
+                }
+                if (!pfns[pfn]) {
+                    pfns[pfn] = type + 1;
+                    stats[type].unique += pagesz;
+                } else if (!(pfns[pfn] & kPfnShared)) {
+                    stats[pfns[pfn] - 1].shared += pagesz;
+                    pfns[pfn] |= kPfnShared;
+                }
+            }
+        }
+    }
+
+    DedupStats total;
+    ss << ::android::base::StringPrintf("%-12s  %9s  %9s  %9s", "", "RSS sum", "Unique", "Shared")
+       << std::endl;
+    for (int type = 0; type < VMA_TYPE_COUNT; type++) {
+        ss << ::android::base::StringPrintf("%-12s  %8" PRIu64 "K  %8" PRIu64 "K  %8" PRIu64 "K",
+                                            kVmaTypeNames[type], stats[type].rss / 1024,
+                                            stats[type].unique / 1024, stats[type].shared / 1024)
+           << std::endl;
+        total.rss += stats[type].rss;
+        total.unique += stats[type].unique;
+        total.shared += stats[type].shared;
+    }
+    ss << ::android::base::StringPrintf("%-12s  %8" PRIu64 "K  %8" PRIu64 "K  %8" PRIu64 "K",
+                                        "TOTAL", total.rss / 1024, total.unique / 1024,
+                                        total.shared / 1024);
+}
+
 static void print_sysmeminfo(std::stringstream& ss, ::android::meminfo::SysMemInfo& smi) {
     if (has_swap) {
         ss << ::android::base::StringPrintf("ZRAM: %" PRIu64 "K physical used for %" PRIu64
@@ -331,52 +794,68 @@ static void print_sysmeminfo(std::stringstream& ss, ::android::meminfo::SysMemIn
                                         "K slab",
                                         smi.mem_total_kb(), smi.mem_free_kb(), smi.mem_buffers_kb(),
                                         smi.mem_cached_kb(), smi.mem_shmem_kb(), smi.mem_slab_kb());
+
+    if (show_locked) {
+        static constexpr std::string_view kLockTags[] = {
+                ::android::meminfo::SysMemInfo::kMemUnevictable,
+                ::android::meminfo::SysMemInfo::kMemMlocked,
+        };
+        uint64_t lock_kb[std::size(kLockTags)] = {};
+        if (smi.ReadMemInfo(std::size(kLockTags), kLockTags, lock_kb)) {
+            ss << std::endl
+               << ::android::base::StringPrintf("LOCK: %" PRIu64 "K unevictable, %" PRIu64
+                                                "K mlocked",
+                                                lock_kb[0], lock_kb[1]);
+        }
+    }
 }
 
 int main(int argc, char* argv[]) {
-    auto pss_sort = [](ProcessRecord& a, ProcessRecord& b) {
-        MemUsage stats_a = show_wss ? a.Wss() : a.Usage();
-        MemUsage stats_b = show_wss ? b.Wss() : b.Usage();
-        return reverse_sort ? stats_a.pss < stats_b.pss : stats_a.pss > stats_b.pss;
+    auto pss_sort = [](const MemStats& a, const MemStats& b) {
+        return reverse_sort ? a.usage.pss < b.usage.pss : a.usage.pss > b.usage.pss;
+    };
+
+    auto uss_sort = [](const MemStats& a, const MemStats& b) {
+        return reverse_sort ? a.usage.uss < b.usage.uss : a.usage.uss > b.usage.uss;
     };
 
-    auto uss_sort = [](ProcessRecord& a, ProcessRecord& b) {
-        MemUsage stats_a = show_wss ? a.Wss() : a.Usage();
-        MemUsage stats_b = show_wss ? b.Wss() : b.Usage();
-        return reverse_sort ? stats_a.uss < stats_b.uss : stats_a.uss > stats_b.uss;
+    auto rss_sort = [](const MemStats& a, const MemStats& b) {
+        return reverse_sort ? a.usage.rss < b.usage.rss : a.usage.rss > b.usage.rss;
     };
 
-    auto rss_sort = [](ProcessRecord& a, ProcessRecord& b) {
-        MemUsage stats_a = show_wss ? a.Wss() : a.Usage();
-        MemUsage stats_b = show_wss ? b.Wss() : b.Usage();
-        return reverse_sort ? stats_a.rss < stats_b.rss : stats_a.rss > stats_b.rss;
+    auto vss_sort = [](const MemStats& a, const MemStats& b) {
+        return reverse_sort ? a.usage.vss < b.usage.vss : a.usage.vss > b.usage.vss;
     };
 
-    auto vss_sort = [](ProcessRecord& a, ProcessRecord& b) {
-        MemUsage stats_a = show_wss ? a.Wss() : a.Usage();
-        MemUsage stats_b = show_wss ? b.Wss() : b.Usage();
-        return reverse_sort ? stats_a.vss < stats_b.vss : stats_a.vss > stats_b.vss;
+    auto swap_sort = [](const MemStats& a, const MemStats& b) {
+        return reverse_sort ? a.usage.swap < b.usage.swap : a.usage.swap > b.usage.swap;
     };
 
-    auto swap_sort = [](ProcessRecord& a, ProcessRecord& b) {
-        MemUsage stats_a = show_wss ? a.Wss() : a.Usage();
-        MemUsage stats_b = show_wss ? b.Wss() : b.Usage();
-        return reverse_sort ? stats_a.swap < stats_b.swap : stats_a.swap > stats_b.swap;
+    auto oomadj_sort = [](const MemStats& a, const MemStats& b) {
+        return reverse_sort ? a.oomadj < b.oomadj : a.oomadj > b.oomadj;
     };
 
-    auto oomadj_sort = [](ProcessRecord& a, ProcessRecord& b) {
-        return reverse_sort ? a.oomadj() < b.oomadj() : a.oomadj() > b.oomadj();
+    auto locked_sort = [](const MemStats& a, const MemStats& b) {
+        return reverse_sort ? a.locked < b.locked : a.locked > b.locked;
     };
 
     // default PSS sort
-    std::function<bool(ProcessRecord & a, ProcessRecord & b)> proc_sort = pss_sort;
+    std::function<bool(const MemStats& a, const MemStats& b)> proc_sort = pss_sort;
 
     // count all pages by default
     uint64_t pgflags = 0;
     uint64_t pgflags_mask = 0;
 
+    struct option longopts[] = {
+            {"help", no_argument, nullptr, 'h'},
+            {"dedup", no_argument, nullptr, 'd'},
+            {"tree", no_argument, nullptr, 't'},
+            {"group-by", required_argument, nullptr, 'g'},
+            {0, 0, nullptr, 0},
+    };
+
     int opt;
-    while ((opt = getopt(argc, argv, "cChkoprRsuvwW")) != -1) {
+    while ((opt = getopt_long(argc, argv, "cCdhkloprRstuvwW", longopts, nullptr)) != -1) {
         switch (opt) {
             case 'c':
                 pgflags = 0;
@@ -386,12 +865,31 @@ int main(int argc, char* argv[]) {
                 pgflags = (1 << KPF_SWAPBACKED);
                 pgflags_mask = (1 << KPF_SWAPBACKED);
                 break;
+            case 'd':
+                show_dedup = true;
+                break;
+            case 'g':
+                if (!strcmp(optarg, "uid")) {
+                    group_by = GroupBy::UID;
+                } else if (!strcmp(optarg, "package")) {
+                    group_by = GroupBy::PACKAGE;
+                } else if (!strcmp(optarg, "cgroup")) {
+                    group_by = GroupBy::CGROUP;
+                } else {
+                    std::cerr << "Invalid --group-by: " << optarg << std::endl;
+                    usage(EXIT_FAILURE);
+                }
+                break;
             case 'h':
                 usage(EXIT_SUCCESS);
             case 'k':
                 pgflags = (1 << KPF_KSM);
                 pgflags_mask = (1 << KPF_KSM);
                 break;
+            case 'l':
+                proc_sort = locked_sort;
+                show_locked = true;
+                break;
             case 'o':
                 proc_sort = oomadj_sort;
                 show_oomadj = true;
@@ -408,6 +906,9 @@ int main(int argc, char* argv[]) {
             case 's':
                 proc_sort = swap_sort;
                 break;
+            case 't':
+                show_tree = true;
+                break;
             case 'u':
                 proc_sort = uss_sort;
                 break;
@@ -425,6 +926,19 @@ int main(int argc, char* argv[]) {
         }
     }
 
+    if (group_by != GroupBy::NONE && (show_tree || show_oomadj)) {
+        std::cerr << "--group-by can't be combined with -t or -o" << std::endl;
+        usage(EXIT_FAILURE);
+    }
+
+    // Without access to the page flags and counts, the usage can't be read for any process and
+    // the report would be all 0.
+    if (access("/proc/kpageflags", R_OK)) {
+        std::cerr << "procrank requires root: can't read /proc/kpageflags: " << strerror(errno)
+                  << std::endl;
+        exit(EXIT_FAILURE);
+    }
+
     std::vector<pid_t> pids;
     std::vector<ProcessRecord> procs;
     if (reset_wss) {
@@ -463,17 +977,41 @@ int main(int argc, char* argv[]) {
             // directory is inaccessible. It was most likely killed while creating the process
             // record
             std::string procdir = ::android::base::StringPrintf("/proc/%d", pid);
-            if (access(procdir.c_str(), F_OK | R_OK)) return true;
+            if (access(procdir.c_str(), F_OK | R_OK)) {
+                if (process_exited(pid)) {
+                    nr_exited++;
+                } else {
+                    nr_unreadable++;
+                }
+                return true;
+            }
 
             // Warn if we failed to gather process stats even while it is still alive.
             // Return success here, so we continue to print stats for other processes.
             std::cerr << "warning: failed to create process record for: " << pid << std::endl;
+            nr_unreadable++;
             return true;
         }
 
-        // Skip processes with no memory mappings
+        // Skip processes with no memory mappings, e.g. kernel threads. The maps are also left
+        // empty if reading them failed, and the usage stays 0 if the pagemap couldn't be opened
+        // after the maps were read. Count those processes, they either exited in the meantime
+        // or couldn't be read.
         uint64_t vss = show_wss ? proc.Wss().vss : proc.Usage().vss;
-        if (vss == 0) return true;
+        if (!proc.has_maps() || vss == 0) {
+            if (process_exited(pid)) {
+                nr_exited++;
+            } else if (!is_kernel_thread(pid)) {
+                std::cerr << "warning: failed to read the memory usage of: " << pid << std::endl;
+                nr_unreadable++;
+            }
+            return true;
+        }
+
+        // Only read what the selected output needs, each of them reads another /proc file.
+        if (show_tree) proc.ReadParentPid();
+        if (group_by == GroupBy::UID || group_by == GroupBy::PACKAGE) proc.ReadUid();
+        if (show_locked) proc.ReadLocked();
 
         // collect swap_offset counts from all processes in 1st pass
         if (!show_wss && has_swap &&
@@ -509,14 +1047,16 @@ int main(int argc, char* argv[]) {
     }
 
     // Sort all process records, default is PSS descending
-    std::sort(procs.begin(), procs.end(), proc_sort);
+    std::sort(procs.begin(), procs.end(), [&](const ProcessRecord& a, const ProcessRecord& b) {
+        return proc_sort(a.Stats(), b.Stats());
+    });
 
     // start dumping output in string stream
     print_header(ss);
     ss << std::endl;
 
     // 2nd pass to calculate and get per process stats to add them up
-    print_processes(ss, procs, swap_offset_array);
+    print_processes(ss, procs, swap_offset_array, proc_sort);
 
     // Add separator to output
     print_separator(ss);
@@ -524,7 +1064,21 @@ int main(int argc, char* argv[]) {
 
     // Add totals to output
     print_totals(ss);
-    ss << std::endl << std::endl;
+    ss << std::endl;
+    if (nr_exited) {
+        ss << nr_exited << " process(es) exited during the scan and are not included in the totals"
+           << std::endl;
+    }
+    if (nr_unreadable) {
+        ss << nr_unreadable << " process(es) could not be read and are not included in the totals"
+           << std::endl;
+    }
+    ss << std::endl;
+
+    if (show_dedup) {
+        print_dedup_report(ss, procs, smi.mem_total_kb());
+        ss << std::endl << std::endl;
+    }
 
     // Add system information at the end
     print_sysmeminfo(ss, smi);
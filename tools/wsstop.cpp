@@ -14,11 +14,14 @@
  * limitations under the License.
  */
 
+#include <errno.h>
 #include <getopt.h>
 #include <inttypes.h>
 #include <stdio.h>
 #include <stdlib.h>
+#include <string.h>
 #include <sys/types.h>
+#include <time.h>
 #include <unistd.h>
 
 #include <algorithm>
@@ -40,7 +43,7 @@ static pid_t g_pid = -1;
 [[noreturn]] static void usage(int exit_status) {
     fprintf(stderr,
             "%s [-d DELAY_BETWEEN_EACH_SAMPLE] [-n REFRESH_TOTAL] PID\n"
-            "-d\tdelay between each working set sample (default 0)\n"
+            "-d\tdelay between the start of each working set sample (default 0)\n"
             "-n\ttotal number of refreshes before we exit (default 2)\n",
             getprogname());
 
@@ -142,10 +145,44 @@ static void diff_workingset(std::vector<Vma>& wss, std::vector<Vma>& old, std::v
     return;
 }
 
+static bool timespec_before(const struct timespec& a, const struct timespec& b) {
+    return a.tv_sec < b.tv_sec || (a.tv_sec == b.tv_sec && a.tv_nsec < b.tv_nsec);
+}
+
+// Sleep until the next sample is due. Samples are taken on absolute deadlines that are
+// 'g_delay' seconds apart, so the time spent collecting each sample doesn't accumulate as
+// drift across refreshes. Deadlines that were already missed by a slow sample are skipped.
+static void wait_for_next_sample(struct timespec* deadline) {
+    if (g_delay <= 0) {
+        return;
+    }
+
+    struct timespec now;
+    clock_gettime(CLOCK_MONOTONIC, &now);
+    do {
+        deadline->tv_sec += g_delay;
+    } while (timespec_before(*deadline, now));
+
+    while (clock_nanosleep(CLOCK_MONOTONIC, TIMER_ABSTIME, deadline, nullptr) == EINTR) {
+    }
+}
+
+// Returns the total time the device spent suspended since boot, which is the difference
+// between CLOCK_BOOTTIME and CLOCK_MONOTONIC.
+static int64_t suspend_time_ms() {
+    struct timespec boot, mono;
+    clock_gettime(CLOCK_BOOTTIME, &boot);
+    clock_gettime(CLOCK_MONOTONIC, &mono);
+    return (boot.tv_sec - mono.tv_sec) * 1000 + (boot.tv_nsec - mono.tv_nsec) / 1000000;
+}
+
 static int workingset() {
     std::vector<Vma> last_wss = {};
     std::vector<Vma> diff_wss = {};
     uint32_t nr_refresh = 0;
+    struct timespec deadline;
+    clock_gettime(CLOCK_MONOTONIC, &deadline);
+    int64_t last_suspend_ms = suspend_time_ms();
 
     while (true) {
         std::unique_ptr<ProcMemInfo> proc_mem = std::make_unique<ProcMemInfo>(g_pid, true);
@@ -170,8 +207,17 @@ static int workingset() {
         }
 
         last_wss = wss;
-        sleep(g_delay);
+        wait_for_next_sample(&deadline);
         print_divider();
+
+        // Pages aren't accessed while the device is suspended, so make it visible when a
+        // sample covers a suspend instead of reporting it as an ordinary quiet interval.
+        int64_t suspend_ms = suspend_time_ms();
+        if (suspend_ms - last_suspend_ms >= 100) {
+            printf("device was suspended for %" PRId64 " ms since the last sample\n",
+                   suspend_ms - last_suspend_ms);
+        }
+        last_suspend_ms = suspend_ms;
     }
 
     return 0;
@@ -215,5 +261,12 @@ int main(int argc, char* argv[]) {
         return 0;
     }
 
+    // The idle bits of the pages are set and read through the bitmap, which only root can do.
+    if (access("/sys/kernel/mm/page_idle/bitmap", R_OK | W_OK)) {
+        fprintf(stderr, "wsstop requires root: can't access /sys/kernel/mm/page_idle/bitmap: %s\n",
+                strerror(errno));
+        return EXIT_FAILURE;
+    }
+
     return workingset();
 }